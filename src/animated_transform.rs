@@ -0,0 +1,454 @@
+use std::borrow::Cow;
+
+use crate::{BoundingBox, Matrix4x4, Transform, Vect};
+
+/// Number of instants at which the transformation is evaluated between two
+/// keyframes to compute the box covering a motion
+const BOUNDS_STEPS: usize = 32;
+
+/// Transformation changing over time, given by its value at some keyframes.
+/// Between two keyframes, the transformations are decomposed into a
+/// translation, a rotation and a scaling, which are interpolated separately so
+/// that rotating objects do not shrink. Before the first keyframe and after
+/// the last one, the transformation does not move.
+#[derive(Clone, Debug)]
+pub struct AnimatedTransform {
+    // Sorted by time
+    keyframes: Vec<Keyframe>,
+}
+
+#[derive(Clone, Debug)]
+struct Keyframe {
+    time: f64,
+    transform: Transform,
+
+    // Decomposition of the transformation: the linear part is rotation *
+    // scaling, where scaling is a symmetric matrix
+    translation: Vect,
+    rotation: Quaternion,
+    scaling: [[f64; 3]; 3],
+}
+
+/// Unit quaternion representing a rotation
+#[derive(Copy, Clone, Debug)]
+struct Quaternion {
+    w: f64,
+    v: Vect,
+}
+
+impl AnimatedTransform {
+    /// Returns a transformation going from start at start_time to end at
+    /// end_time
+    pub fn new(start_time: f64, start: Transform, end_time: f64, end: Transform) -> Self {
+        Self::new_keyframes(vec![(start_time, start), (end_time, end)])
+    }
+
+    /// Returns a transformation going through several keyframes, given as
+    /// (time, transformation)
+    pub fn new_keyframes(mut keyframes: Vec<(f64, Transform)>) -> Self {
+        assert!(!keyframes.is_empty());
+        keyframes.sort_by(|(t1, _), (t2, _)| t1.total_cmp(t2));
+
+        let mut result: Vec<Keyframe> = Vec::with_capacity(keyframes.len());
+
+        for (time, transform) in keyframes {
+            let (translation, mut rotation, scaling) = decompose(transform.matrix());
+
+            // q and -q are the same rotation: choose the one giving the
+            // shortest path from the previous keyframe
+            if let Some(previous) = result.last() {
+                if previous.rotation.dot(&rotation) < 0. {
+                    rotation = rotation.neg();
+                }
+            }
+
+            result.push(Keyframe {
+                time,
+                transform,
+                translation,
+                rotation,
+                scaling,
+            });
+        }
+
+        Self { keyframes: result }
+    }
+
+    /// Returns true if the transformation changes over time
+    pub fn is_animated(&self) -> bool {
+        self.keyframes.len() > 1
+    }
+
+    /// Returns the transformation at a given time. Outside the keyframes it is
+    /// borrowed, but between two keyframes each call interpolates the
+    /// rotation with a slerp and inverts the resulting matrix. The animated
+    /// primitives pay this once per ray.
+    pub fn at(&self, time: f64) -> Cow<'_, Transform> {
+        let first = &self.keyframes[0];
+        let last = &self.keyframes[self.keyframes.len() - 1];
+
+        if time <= first.time {
+            return Cow::Borrowed(&first.transform);
+        }
+        if time >= last.time {
+            return Cow::Borrowed(&last.transform);
+        }
+
+        // Index of the last keyframe before time
+        let index = self.keyframes.partition_point(|k| k.time <= time) - 1;
+        let k0 = &self.keyframes[index];
+        let k1 = &self.keyframes[index + 1];
+
+        let alpha = (time - k0.time) / (k1.time - k0.time);
+
+        let translation = (1. - alpha) * k0.translation + alpha * k1.translation;
+        let rotation = k0.rotation.slerp(&k1.rotation, alpha).matrix();
+
+        let mut scaling = [[0.; 3]; 3];
+        for (i, line) in scaling.iter_mut().enumerate() {
+            for (j, entry) in line.iter_mut().enumerate() {
+                *entry = (1. - alpha) * k0.scaling[i][j] + alpha * k1.scaling[i][j];
+            }
+        }
+
+        let linear = mul3(&rotation, &scaling);
+        let mat = Matrix4x4::new([
+            [linear[0][0], linear[0][1], linear[0][2], translation.x],
+            [linear[1][0], linear[1][1], linear[1][2], translation.y],
+            [linear[2][0], linear[2][1], linear[2][2], translation.z],
+        ]);
+
+        Cow::Owned(Transform::new_from_matrix(mat))
+    }
+
+    /// Returns a box containing the images of a box at every time
+    pub fn motion_bounding_box(&self, bounding_box: &BoundingBox) -> BoundingBox {
+        let mut result = self.keyframes[0].transform.apply_bounding_box(bounding_box);
+
+        if !self.is_animated() || !bounding_box.is_finite() {
+            return result;
+        }
+        let Some((min, max)) = bounding_box.extremities() else {
+            return result;
+        };
+
+        let corners: Vec<Vect> = (0..8)
+            .map(|i| {
+                Vect::new(
+                    if i & 1 == 0 { min.x } else { max.x },
+                    if i & 2 == 0 { min.y } else { max.y },
+                    if i & 4 == 0 { min.z } else { max.z },
+                )
+            })
+            .collect();
+
+        // Between two sampled instants, the corners follow an arc instead of
+        // a segment when the object rotates. The box is enlarged by the
+        // distance between the arc and the segment.
+        let mut margin: f64 = 0.;
+
+        for pair in self.keyframes.windows(2) {
+            let (k0, k1) = (&pair[0], &pair[1]);
+
+            let mut previous = k0.transform.clone();
+            for step in 1..=BOUNDS_STEPS {
+                let time = k0.time + (k1.time - k0.time) * step as f64 / BOUNDS_STEPS as f64;
+                let current = self.at(time).into_owned();
+                result = &result + &current.apply_bounding_box(bounding_box);
+
+                let angle = rotation_angle(previous.matrix(), current.matrix());
+                for &corner in &corners {
+                    let chord = (current.apply_point(corner) - previous.apply_point(corner)).norm();
+                    margin = margin.max(0.5 * chord * (0.25 * angle).tan());
+                }
+
+                previous = current;
+            }
+        }
+
+        match result.extremities() {
+            Some((min, max)) if margin > 0. => {
+                let margin = Vect::new(margin, margin, margin);
+                BoundingBox::new_from_extremities(min - margin, max + margin)
+            }
+            _ => result,
+        }
+    }
+}
+
+impl From<Transform> for AnimatedTransform {
+    fn from(transform: Transform) -> Self {
+        Self::new_keyframes(vec![(0., transform)])
+    }
+}
+
+impl Quaternion {
+    /// Returns the quaternion of a rotation matrix
+    fn from_matrix(r: &[[f64; 3]; 3]) -> Self {
+        let trace = r[0][0] + r[1][1] + r[2][2];
+
+        let (w, x, y, z) = if trace > 0. {
+            let s = 2. * (trace + 1.).sqrt();
+            (
+                0.25 * s,
+                (r[2][1] - r[1][2]) / s,
+                (r[0][2] - r[2][0]) / s,
+                (r[1][0] - r[0][1]) / s,
+            )
+        } else if r[0][0] > r[1][1] && r[0][0] > r[2][2] {
+            let s = 2. * (1. + r[0][0] - r[1][1] - r[2][2]).sqrt();
+            (
+                (r[2][1] - r[1][2]) / s,
+                0.25 * s,
+                (r[0][1] + r[1][0]) / s,
+                (r[0][2] + r[2][0]) / s,
+            )
+        } else if r[1][1] > r[2][2] {
+            let s = 2. * (1. + r[1][1] - r[0][0] - r[2][2]).sqrt();
+            (
+                (r[0][2] - r[2][0]) / s,
+                (r[0][1] + r[1][0]) / s,
+                0.25 * s,
+                (r[1][2] + r[2][1]) / s,
+            )
+        } else {
+            let s = 2. * (1. + r[2][2] - r[0][0] - r[1][1]).sqrt();
+            (
+                (r[1][0] - r[0][1]) / s,
+                (r[0][2] + r[2][0]) / s,
+                (r[1][2] + r[2][1]) / s,
+                0.25 * s,
+            )
+        };
+
+        Self {
+            w,
+            v: Vect::new(x, y, z),
+        }
+        .normalized()
+    }
+
+    /// Returns the rotation matrix of the quaternion
+    fn matrix(&self) -> [[f64; 3]; 3] {
+        let (w, x, y, z) = (self.w, self.v.x, self.v.y, self.v.z);
+
+        [
+            [
+                1. - 2. * (y * y + z * z),
+                2. * (x * y - w * z),
+                2. * (x * z + w * y),
+            ],
+            [
+                2. * (x * y + w * z),
+                1. - 2. * (x * x + z * z),
+                2. * (y * z - w * x),
+            ],
+            [
+                2. * (x * z - w * y),
+                2. * (y * z + w * x),
+                1. - 2. * (x * x + y * y),
+            ],
+        ]
+    }
+
+    fn dot(&self, other: &Self) -> f64 {
+        self.w * other.w + self.v * other.v
+    }
+
+    fn neg(&self) -> Self {
+        Self {
+            w: -self.w,
+            v: -self.v,
+        }
+    }
+
+    fn normalized(&self) -> Self {
+        let norm = self.dot(self).sqrt();
+        Self {
+            w: self.w / norm,
+            v: (1. / norm) * self.v,
+        }
+    }
+
+    /// Spherical linear interpolation between two rotations
+    fn slerp(&self, other: &Self, alpha: f64) -> Self {
+        let cos_theta = self.dot(other).clamp(-1., 1.);
+
+        let (a, b) = if cos_theta > 0.9995 {
+            // The rotations are almost the same: use a linear interpolation
+            (1. - alpha, alpha)
+        } else {
+            let theta = cos_theta.acos();
+            (
+                ((1. - alpha) * theta).sin() / theta.sin(),
+                (alpha * theta).sin() / theta.sin(),
+            )
+        };
+
+        Self {
+            w: a * self.w + b * other.w,
+            v: a * self.v + b * other.v,
+        }
+        .normalized()
+    }
+}
+
+/// Decomposes an affine transformation into a translation, a rotation and a
+/// symmetric scaling, using the polar decomposition of its linear part
+fn decompose(mat: &Matrix4x4) -> (Vect, Quaternion, [[f64; 3]; 3]) {
+    let m = &mat.m;
+    let translation = Vect::new(m[0][3], m[1][3], m[2][3]);
+
+    let linear = [
+        [m[0][0], m[0][1], m[0][2]],
+        [m[1][0], m[1][1], m[1][2]],
+        [m[2][0], m[2][1], m[2][2]],
+    ];
+
+    // The rotation is the limit of R <- (R + R^-T) / 2
+    let mut rotation = linear;
+    for _ in 0..100 {
+        let inv_t = transpose3(&inverse3(&rotation));
+
+        let mut next = [[0.; 3]; 3];
+        let mut diff: f64 = 0.;
+        for i in 0..3 {
+            for j in 0..3 {
+                next[i][j] = 0.5 * (rotation[i][j] + inv_t[i][j]);
+                diff = diff.max((next[i][j] - rotation[i][j]).abs());
+            }
+        }
+
+        rotation = next;
+        if diff <= 1e-12 {
+            break;
+        }
+    }
+
+    // A reflection is kept in the scaling, so that the rotation is proper
+    if det3(&rotation) < 0. {
+        for line in rotation.iter_mut() {
+            for entry in line.iter_mut() {
+                *entry = -*entry;
+            }
+        }
+    }
+
+    let scaling = mul3(&transpose3(&rotation), &linear);
+
+    (translation, Quaternion::from_matrix(&rotation), scaling)
+}
+
+/// Returns the angle of the rotation between the linear parts of two
+/// transformations
+fn rotation_angle(m1: &Matrix4x4, m2: &Matrix4x4) -> f64 {
+    let q1 = decompose(m1).1;
+    let q2 = decompose(m2).1;
+    2. * q1.dot(&q2).abs().min(1.).acos()
+}
+
+fn mul3(a: &[[f64; 3]; 3], b: &[[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    let mut m = [[0.; 3]; 3];
+    for (i, line) in m.iter_mut().enumerate() {
+        for (j, entry) in line.iter_mut().enumerate() {
+            *entry = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    m
+}
+
+fn transpose3(a: &[[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    let mut m = [[0.; 3]; 3];
+    for (i, line) in m.iter_mut().enumerate() {
+        for (j, entry) in line.iter_mut().enumerate() {
+            *entry = a[j][i];
+        }
+    }
+    m
+}
+
+fn det3(a: &[[f64; 3]; 3]) -> f64 {
+    a[0][0] * (a[1][1] * a[2][2] - a[1][2] * a[2][1])
+        - a[0][1] * (a[1][0] * a[2][2] - a[1][2] * a[2][0])
+        + a[0][2] * (a[1][0] * a[2][1] - a[1][1] * a[2][0])
+}
+
+fn inverse3(a: &[[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    let inv = Matrix4x4::new([
+        [a[0][0], a[0][1], a[0][2], 0.],
+        [a[1][0], a[1][1], a[1][2], 0.],
+        [a[2][0], a[2][1], a[2][2], 0.],
+    ])
+    .inverse();
+
+    let m = &inv.m;
+    [
+        [m[0][0], m[0][1], m[0][2]],
+        [m[1][0], m[1][1], m[1][2]],
+        [m[2][0], m[2][1], m[2][2]],
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn test_interpolation() {
+        let start = Transform::new_scaling(1., 2., 3.)
+            .add(&Transform::new_translation(Vect::new(1., 0., 0.)));
+        let end = Transform::new_z_rotation(0.5 * PI)
+            .add(&Transform::new_translation(Vect::new(1., 4., 0.)));
+        let middle =
+            Transform::new_z_rotation(0.3).add(&Transform::new_translation(Vect::new(0., 2., 0.)));
+
+        let animated = AnimatedTransform::new_keyframes(vec![
+            (2., end.clone()),
+            (0., start.clone()),
+            (1., middle.clone()),
+        ]);
+
+        // The keyframes are reproduced
+        let p = Vect::new(0.3, -0.7, 1.1);
+        for (time, transform) in [(0., &start), (1., &middle), (2., &end), (3., &end)] {
+            let expected = transform.apply_point(p);
+            assert!((animated.at(time).apply_point(p) - expected).norm() <= 1e-9);
+        }
+
+        // The rotation is interpolated without shrinking the object
+        let animated = AnimatedTransform::new(
+            0.,
+            Transform::new_identity(),
+            1.,
+            Transform::new_z_rotation(0.5 * PI),
+        );
+        let p = animated.at(0.5).apply_point(Vect::new(1., 0., 0.));
+        let expected = Vect::new(1., 1., 0.).normalized();
+        assert!((p - expected).norm() <= 1e-9);
+    }
+
+    #[test]
+    fn test_motion_bounding_box() {
+        let animated = AnimatedTransform::new(
+            0.,
+            Transform::new_identity(),
+            1.,
+            Transform::new_z_rotation(PI).add(&Transform::new_translation(Vect::new(3., 0., 1.))),
+        );
+        let bounding_box = BoundingBox::new_from_extremities(Vect::zero(), Vect::new(1., 1., 1.));
+        let (min, max) = animated
+            .motion_bounding_box(&bounding_box)
+            .extremities()
+            .unwrap();
+
+        for i in 0..=1000 {
+            let transform = animated.at(i as f64 / 1000.);
+            for corner in [Vect::new(1., 0., 0.), Vect::new(1., 1., 1.)] {
+                let p = transform.apply_point(corner);
+                assert!(min.x <= p.x && min.y <= p.y && min.z <= p.z);
+                assert!(p.x <= max.x && p.y <= max.y && p.z <= max.z);
+            }
+        }
+    }
+}
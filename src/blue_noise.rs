@@ -0,0 +1,162 @@
+use std::sync::OnceLock;
+
+use fastrand::Rng;
+
+/// Size of the side of the tile
+pub const TILE_SIZE: usize = 64;
+
+/// Standard deviation of the Gaussian used to measure the clustering of the
+/// points, in pixels
+const SIGMA: f64 = 1.9;
+
+static TILE: OnceLock<Vec<f64>> = OnceLock::new();
+
+/// Returns the value of the blue-noise tile at a pixel. The tile is repeated
+/// over the whole plane. Its values are uniformly distributed in [0; 1], and
+/// neighbouring pixels tend to have very different values, so that the noise
+/// has no low frequencies.
+pub fn blue_noise(x: usize, y: usize) -> f64 {
+    let tile = TILE.get_or_init(generate_tile);
+    tile[(y % TILE_SIZE) * TILE_SIZE + x % TILE_SIZE]
+}
+
+/// Generates the tile with the void-and-cluster method (Ulichney, 1993): the
+/// pixels are ranked by inserting points one by one in the largest void, and
+/// the value of a pixel is its rank.
+fn generate_tile() -> Vec<f64> {
+    let n = TILE_SIZE * TILE_SIZE;
+    let mut rng = Rng::with_seed(0);
+
+    // Energy contributed by a point to the pixels at each toroidal offset
+    let kernel: Vec<f64> = (0..n)
+        .map(|index| {
+            let distance = |d: usize| d.min(TILE_SIZE - d) as f64;
+            let dx = distance(index % TILE_SIZE);
+            let dy = distance(index / TILE_SIZE);
+            (-(dx * dx + dy * dy) / (2. * SIGMA * SIGMA)).exp()
+        })
+        .collect();
+
+    let mut pattern = Pattern {
+        points: vec![false; n],
+        energy: vec![0.; n],
+        kernel,
+    };
+
+    // Initial pattern: random points, moved from the tightest cluster to the
+    // largest void until it is stable
+    let initial_count = n / 10;
+    while pattern.count() < initial_count {
+        let index = rng.usize(..n);
+        if !pattern.points[index] {
+            pattern.toggle(index);
+        }
+    }
+    loop {
+        let cluster = pattern.tightest_cluster();
+        pattern.toggle(cluster);
+        let void = pattern.largest_void();
+        pattern.toggle(void);
+
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut ranks = vec![0; n];
+
+    // The points of the initial pattern are ranked by removing them from the
+    // tightest cluster
+    let mut removed = pattern.clone();
+    for rank in (0..initial_count).rev() {
+        let cluster = removed.tightest_cluster();
+        removed.toggle(cluster);
+        ranks[cluster] = rank;
+    }
+
+    // The other pixels are ranked by filling the largest void
+    for rank in initial_count..n {
+        let void = pattern.largest_void();
+        pattern.toggle(void);
+        ranks[void] = rank;
+    }
+
+    ranks
+        .into_iter()
+        .map(|rank| (rank as f64 + 0.5) / n as f64)
+        .collect()
+}
+
+#[derive(Clone)]
+struct Pattern {
+    points: Vec<bool>,
+
+    // Sum of the kernel over the points of the pattern, at each pixel
+    energy: Vec<f64>,
+    kernel: Vec<f64>,
+}
+
+impl Pattern {
+    fn count(&self) -> usize {
+        self.points.iter().filter(|&&p| p).count()
+    }
+
+    /// Adds or removes a point
+    fn toggle(&mut self, index: usize) {
+        self.points[index] = !self.points[index];
+        let sign = if self.points[index] { 1. } else { -1. };
+
+        let (x, y) = (index % TILE_SIZE, index / TILE_SIZE);
+        for (other, energy) in self.energy.iter_mut().enumerate() {
+            let dx = (other % TILE_SIZE + TILE_SIZE - x) % TILE_SIZE;
+            let dy = (other / TILE_SIZE + TILE_SIZE - y) % TILE_SIZE;
+            *energy += sign * self.kernel[dy * TILE_SIZE + dx];
+        }
+    }
+
+    /// Returns the point with the highest energy
+    fn tightest_cluster(&self) -> usize {
+        (0..self.points.len())
+            .filter(|&i| self.points[i])
+            .max_by(|&a, &b| self.energy[a].total_cmp(&self.energy[b]))
+            .unwrap()
+    }
+
+    /// Returns the empty pixel with the lowest energy
+    fn largest_void(&self) -> usize {
+        (0..self.points.len())
+            .filter(|&i| !self.points[i])
+            .min_by(|&a, &b| self.energy[a].total_cmp(&self.energy[b]))
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blue_noise_tile() {
+        let n = TILE_SIZE * TILE_SIZE;
+
+        // Each value is used once
+        let mut counts = vec![0; n];
+        for y in 0..TILE_SIZE {
+            for x in 0..TILE_SIZE {
+                counts[(blue_noise(x, y) * n as f64) as usize] += 1;
+            }
+        }
+        assert!(counts.iter().all(|&count| count == 1));
+
+        // Neighbouring values are further apart than with white noise, for
+        // which the mean difference would be 1/3
+        let mut difference = 0.;
+        for y in 0..TILE_SIZE {
+            for x in 0..TILE_SIZE {
+                difference += (blue_noise(x, y) - blue_noise(x + 1, y)).abs();
+                difference += (blue_noise(x, y) - blue_noise(x, y + 1)).abs();
+            }
+        }
+        assert!(difference / (2 * n) as f64 > 0.37);
+    }
+}
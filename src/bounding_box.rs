@@ -35,6 +35,27 @@ impl BoundingBox {
         }
     }
 
+    /// Returns the vertices with the minimal and the maximal coordinates, or
+    /// None if the box is empty
+    pub fn extremities(&self) -> Option<(Vect, Vect)> {
+        self.extremities
+    }
+
+    /// Returns true if the box is empty or does not extend to infinity
+    pub fn is_finite(&self) -> bool {
+        match self.extremities {
+            None => true,
+            Some((min, max)) => {
+                min.x.is_finite()
+                    && min.y.is_finite()
+                    && min.z.is_finite()
+                    && max.x.is_finite()
+                    && max.y.is_finite()
+                    && max.z.is_finite()
+            }
+        }
+    }
+
     pub fn add_point(&mut self, point: Vect) {
         if let Some((min, max)) = &mut self.extremities {
             min.x = f64::min(point.x, min.x);
@@ -49,7 +70,9 @@ impl BoundingBox {
         }
     }
 
-    pub fn collision_date(&self, ray: Ray) -> Option<f64> {
+    /// Returns the dates at which a ray enters and leaves the box. If the ray
+    /// starts inside the box, the first date is 0.
+    pub fn collision_interval(&self, ray: Ray) -> Option<(f64, f64)> {
         if let Some((min, max)) = self.extremities {
             let mut t_min = -f64::INFINITY;
             let mut t_max = f64::INFINITY;
@@ -64,7 +87,7 @@ impl BoundingBox {
             }
 
             if t_min <= t_max && t_max >= 0. {
-                Some(f64::max(t_min, 0.))
+                Some((f64::max(t_min, 0.), t_max))
             } else {
                 None
             }
@@ -72,6 +95,16 @@ impl BoundingBox {
             None
         }
     }
+
+    pub fn collision_date(&self, ray: Ray) -> Option<f64> {
+        self.collision_interval(ray).map(|(t_min, _)| t_min)
+    }
+}
+
+impl Default for BoundingBox {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Add for &BoundingBox {
@@ -0,0 +1,247 @@
+use crate::{stats, BoundingBox, Ray, Vect};
+use std::mem::size_of;
+
+/// Maximal number of objects in a leaf
+const LEAF_SIZE: usize = 4;
+
+/// Bounding volume hierarchy over a set of objects, used to find quickly the
+/// objects that a ray may hit. The objects are only known by their index and
+/// their bounding box.
+#[derive(Clone, Debug)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+
+    // Indices of the objects, ordered so that the objects of each leaf are
+    // contiguous
+    indices: Vec<usize>,
+
+    // Objects whose bounding box is infinite. They are tested by every ray.
+    unbounded: Vec<usize>,
+}
+
+#[derive(Clone, Debug)]
+struct Node {
+    bounding_box: BoundingBox,
+    content: NodeContent,
+}
+
+#[derive(Clone, Debug)]
+enum NodeContent {
+    // The objects are indices[first..first + count]
+    Leaf { first: usize, count: usize },
+    Inner { left: usize, right: usize },
+}
+
+impl Bvh {
+    /// Builds a hierarchy over objects, given their bounding boxes
+    pub fn new(bounding_boxes: &[BoundingBox]) -> Self {
+        let mut bvh = Self {
+            nodes: Vec::new(),
+            indices: Vec::new(),
+            unbounded: Vec::new(),
+        };
+
+        let mut objects = Vec::new();
+        for (index, bounding_box) in bounding_boxes.iter().enumerate() {
+            if !bounding_box.is_finite() {
+                bvh.unbounded.push(index);
+            } else if let Some((min, max)) = bounding_box.extremities() {
+                objects.push((index, 0.5 * (min + max)));
+            }
+            // Empty boxes are never hit
+        }
+
+        if !objects.is_empty() {
+            bvh.build(bounding_boxes, &mut objects, 0);
+        }
+        bvh.indices = objects.iter().map(|&(index, _)| index).collect();
+
+        bvh
+    }
+
+    /// Builds the subtree containing some objects, given with their centers,
+    /// and returns the index of its root. The objects are reordered, and the
+    /// first one will be at the position first in the indices.
+    fn build(
+        &mut self,
+        bounding_boxes: &[BoundingBox],
+        objects: &mut [(usize, Vect)],
+        first: usize,
+    ) -> usize {
+        let mut bounding_box = BoundingBox::new();
+        let mut centers = BoundingBox::new();
+        for &(index, center) in objects.iter() {
+            bounding_box = &bounding_box + &bounding_boxes[index];
+            centers.add_point(center);
+        }
+
+        let node_index = self.nodes.len();
+        self.nodes.push(Node {
+            bounding_box,
+            content: NodeContent::Leaf {
+                first,
+                count: objects.len(),
+            },
+        });
+
+        if objects.len() <= LEAF_SIZE {
+            return node_index;
+        }
+
+        // Split along the largest dimension of the centers, at the median
+        let (min, max) = centers.extremities().unwrap();
+        let extent = max - min;
+        let axis = if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        };
+
+        if extent.component(axis) == 0. {
+            // All the objects have the same center
+            return node_index;
+        }
+
+        let middle = objects.len() / 2;
+        objects.select_nth_unstable_by(middle, |(_, a), (_, b)| {
+            a.component(axis).total_cmp(&b.component(axis))
+        });
+
+        let (left_objects, right_objects) = objects.split_at_mut(middle);
+        let left = self.build(bounding_boxes, left_objects, first);
+        let right = self.build(bounding_boxes, right_objects, first + middle);
+
+        self.nodes[node_index].content = NodeContent::Inner { left, right };
+
+        node_index
+    }
+
+    /// Returns the index of the first object hit by a ray, and the date of the
+    /// collision. The function collision_date(index) returns the date at which
+    /// the ray hits the object of given index.
+    pub fn first_collision(
+        &self,
+        ray: Ray,
+        mut collision_date: impl FnMut(usize) -> Option<f64>,
+    ) -> Option<(usize, f64)> {
+        let mut best: Option<(usize, f64)> = None;
+
+        // Intersection tests, for the statistics
+        let (mut node_tests, mut primitive_tests) = (0, 0);
+
+        let mut test = |index: usize, best: &mut Option<(usize, f64)>| {
+            primitive_tests += 1;
+            if let Some(date) = collision_date(index) {
+                if best.is_none_or(|(_, best_date)| date < best_date) {
+                    *best = Some((index, date));
+                }
+            }
+        };
+
+        for &index in &self.unbounded {
+            test(index, &mut best);
+        }
+
+        if self.nodes.is_empty() {
+            stats::count_tests(0, self.unbounded.len() as u64);
+            return best;
+        }
+
+        // Nodes to visit, with the date at which the ray enters them
+        let mut stack = Vec::new();
+        node_tests += 1;
+        if let Some(date) = self.nodes[0].bounding_box.collision_date(ray) {
+            stack.push((0, date));
+        }
+
+        while let Some((node_index, entry_date)) = stack.pop() {
+            if let Some((_, best_date)) = best {
+                if entry_date >= best_date {
+                    continue;
+                }
+            }
+
+            match self.nodes[node_index].content {
+                NodeContent::Leaf { first, count } => {
+                    for &index in &self.indices[first..first + count] {
+                        test(index, &mut best);
+                    }
+                }
+                NodeContent::Inner { left, right } => {
+                    let left_date = self.nodes[left].bounding_box.collision_date(ray);
+                    let right_date = self.nodes[right].bounding_box.collision_date(ray);
+                    node_tests += 2;
+
+                    // The nearest child is visited first
+                    match (left_date, right_date) {
+                        (Some(l), Some(r)) => {
+                            if l < r {
+                                stack.push((right, r));
+                                stack.push((left, l));
+                            } else {
+                                stack.push((left, l));
+                                stack.push((right, r));
+                            }
+                        }
+                        (Some(l), None) => stack.push((left, l)),
+                        (None, Some(r)) => stack.push((right, r)),
+                        (None, None) => {}
+                    }
+                }
+            }
+        }
+
+        stats::count_tests(node_tests, primitive_tests);
+        best
+    }
+
+    /// Returns the memory used by the hierarchy, in bytes
+    pub fn memory_size(&self) -> usize {
+        size_of::<Self>()
+            + self.nodes.capacity() * size_of::<Node>()
+            + (self.indices.capacity() + self.unbounded.capacity()) * size_of::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{shapes::Sphere, Shape};
+    use fastrand::Rng;
+
+    #[test]
+    fn test_first_collision() {
+        let mut rng = Rng::with_seed(42);
+
+        let mut spheres = Vec::new();
+        for _ in 0..200 {
+            let center = Vect::new(10. * rng.f64(), 10. * rng.f64(), 10. * rng.f64());
+            spheres.push(Sphere::new(center, 0.5 * rng.f64()));
+        }
+        let bounding_boxes: Vec<BoundingBox> = spheres.iter().map(|s| s.bounding_box()).collect();
+        let bvh = Bvh::new(&bounding_boxes);
+
+        for _ in 0..200 {
+            let pos = Vect::new(20. * rng.f64() - 5., 20. * rng.f64() - 5., -5.);
+            let dir = Vect::new(rng.f64() - 0.5, rng.f64() - 0.5, 1.);
+            let ray = Ray::new(pos, dir);
+
+            let mut expected: Option<(usize, f64)> = None;
+            for (index, sphere) in spheres.iter().enumerate() {
+                if let Some(date) = sphere.collision_date(ray) {
+                    if expected.is_none_or(|(_, d)| date < d) {
+                        expected = Some((index, date));
+                    }
+                }
+            }
+
+            let found = bvh.first_collision(ray, |index| spheres[index].collision_date(ray));
+            assert_eq!(
+                found.map(|(index, _)| index),
+                expected.map(|(index, _)| index)
+            );
+        }
+    }
+}
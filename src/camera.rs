@@ -1,33 +1,75 @@
-use std::cmp::min;
+use std::fmt::Debug;
 
-use crate::{Ray, Vect};
+use crate::{vect::complete_basis_from_1, Ray, Vect};
 
-#[derive(Clone, Debug)]
-pub struct Camera {
-    // Position of the focus point
-    pub pos: Vect,
+/// A camera maps the points of the image to rays.
+pub trait Camera: Send + Sync + Debug + CameraClone {
+    /// Returns the width of the image, in pixels
+    fn width(&self) -> usize;
 
-    // Direction where the camera looks. The norm of the vector is the focal
-    // distance of the camera.
-    pub dir: Vect,
+    /// Returns the height of the image, in pixels
+    fn height(&self) -> usize;
 
-    // Dimensions of the image
-    pub width: usize,
-    pub height: usize,
+    /// Returns the ray going through a point of the image, or None if the
+    /// projection does not cover this point. The coordinates are in pixels,
+    /// and (0, 0) is the bottom left corner of the image. The lens sample is
+    /// used by cameras with a lens; the sample [0, 0] is the center of the
+    /// lens. The time sample, in [0, 1], gives the time of the ray in the
+    /// shutter interval of the camera.
+    fn generate_ray(&self, x: f64, y: f64, lens_sample: [f64; 2], time_sample: f64) -> Option<Ray>;
 }
 
-impl Camera {
-    pub fn generate_ray(&self, x: usize, y: usize) -> Ray {
-        let ratio = min(self.width, self.height) as f64;
+pub trait CameraClone {
+    fn clone_box(&self) -> Box<dyn Camera>;
+}
+
+impl<T: Camera + Clone + 'static> CameraClone for T {
+    fn clone_box(&self) -> Box<dyn Camera> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn Camera> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
 
-        let i = Vect::new(self.dir.y, -self.dir.x, 0.).normalized();
-        let j = -(self.dir ^ i).normalized();
-        assert!(j.z >= 0.);
+/// Interval of time during which the shutter of a camera is open. Objects
+/// moving during this interval are blurred.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Shutter {
+    pub open: f64,
+    pub close: f64,
+}
 
-        let dir = self.dir
-            + ((x as f64) - 0.5 * (self.width as f64)) / ratio * i
-            + ((y as f64) - 0.5 * (self.height as f64)) / ratio * j;
+impl Shutter {
+    pub fn new(open: f64, close: f64) -> Self {
+        assert!(open <= close);
+        Self { open, close }
+    }
 
-        Ray::new(self.pos, dir)
+    /// Returns the time corresponding to a sample in [0, 1]
+    pub fn time(&self, sample: f64) -> f64 {
+        self.open + sample * (self.close - self.open)
     }
 }
+
+/// Returns the orthonormal basis (right, up, forward) of a camera placed at
+/// eye and looking at target. The up vector gives the vertical direction of
+/// the image, and does not need to be orthogonal to the direction of the
+/// camera: tilting it rolls the camera.
+pub fn look_at_basis(eye: Vect, target: Vect, up: Vect) -> [Vect; 3] {
+    let forward = (target - eye).normalized();
+
+    let mut right = forward ^ up;
+    if right.norm() <= 1e-9 * up.norm() {
+        // The up vector is parallel to the direction of the camera: any
+        // orientation is valid
+        right = complete_basis_from_1(forward)[1];
+    }
+    let right = right.normalized();
+    let up = right ^ forward;
+
+    [right, up, forward]
+}
@@ -0,0 +1,144 @@
+use crate::{
+    camera::{look_at_basis, Shutter},
+    Camera, Ray, Vect,
+};
+
+/// Mapping between the angle of a ray with the axis of a fisheye camera and
+/// the distance to the center of the image
+#[derive(Copy, Clone, Debug)]
+pub enum FisheyeProjection {
+    /// The distance is proportional to the angle
+    Equidistant,
+
+    /// The area of the image is proportional to the solid angle
+    Equisolid,
+}
+
+/// Circular fisheye camera. The circle covered by the projection is inscribed
+/// in the image, and the points outside of it are not rendered.
+#[derive(Clone, Debug)]
+pub struct FisheyeCamera {
+    pos: Vect,
+
+    // Orthonormal basis of the camera
+    right: Vect,
+    up: Vect,
+    forward: Vect,
+
+    // Half the field of view, in radians
+    half_fov: f64,
+    projection: FisheyeProjection,
+
+    shutter: Shutter,
+
+    // Dimensions of the image
+    width: usize,
+    height: usize,
+}
+
+impl FisheyeCamera {
+    /// Creates a camera placed at eye and looking at target. The field of
+    /// view is given in degrees, and can be up to 360 degrees.
+    pub fn new(
+        eye: Vect,
+        target: Vect,
+        up: Vect,
+        fov: f64,
+        projection: FisheyeProjection,
+        width: usize,
+        height: usize,
+    ) -> Box<Self> {
+        assert!(fov > 0. && fov <= 360.);
+
+        let [right, up, forward] = look_at_basis(eye, target, up);
+
+        Box::new(Self {
+            pos: eye,
+            right,
+            up,
+            forward,
+            half_fov: 0.5 * fov.to_radians(),
+            projection,
+            shutter: Shutter::default(),
+            width,
+            height,
+        })
+    }
+
+    /// Keeps the shutter open between two times, to get motion blur. By
+    /// default, the shutter opens and closes at time 0.
+    pub fn with_shutter(mut self: Box<Self>, open: f64, close: f64) -> Box<Self> {
+        self.shutter = Shutter::new(open, close);
+        self
+    }
+}
+
+impl Camera for FisheyeCamera {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn generate_ray(
+        &self,
+        x: f64,
+        y: f64,
+        _lens_sample: [f64; 2],
+        time_sample: f64,
+    ) -> Option<Ray> {
+        let radius = 0.5 * self.width.min(self.height) as f64;
+        let dx = (x - 0.5 * self.width as f64) / radius;
+        let dy = (y - 0.5 * self.height as f64) / radius;
+
+        // Distance to the center, in [0; 1] inside the circle
+        let r = (dx * dx + dy * dy).sqrt();
+        if r > 1. {
+            return None;
+        }
+
+        // Angle between the ray and the axis of the camera
+        let theta = match self.projection {
+            FisheyeProjection::Equidistant => r * self.half_fov,
+            FisheyeProjection::Equisolid => 2. * (r * (0.5 * self.half_fov).sin()).asin(),
+        };
+        let phi = dy.atan2(dx);
+
+        let dir = theta.cos() * self.forward
+            + (theta.sin() * phi.cos()) * self.right
+            + (theta.sin() * phi.sin()) * self.up;
+
+        Some(Ray::new(self.pos, dir).with_time(self.shutter.time(time_sample)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fisheye() {
+        for projection in [FisheyeProjection::Equidistant, FisheyeProjection::Equisolid] {
+            let camera = FisheyeCamera::new(
+                Vect::zero(),
+                Vect::new(1., 0., 0.),
+                Vect::new(0., 0., 1.),
+                180.,
+                projection,
+                200,
+                100,
+            );
+
+            let ray = camera.generate_ray(100., 50., [0., 0.], 0.).unwrap();
+            assert!((ray.dir - Vect::new(1., 0., 0.)).norm() <= 1e-9);
+
+            // The border of the circle is at half the field of view
+            let ray = camera.generate_ray(100., 100., [0., 0.], 0.).unwrap();
+            assert!((ray.dir - Vect::new(0., 0., 1.)).norm() <= 1e-9);
+
+            assert!(camera.generate_ray(10., 50., [0., 0.], 0.).is_none());
+        }
+    }
+}
@@ -0,0 +1,9 @@
+pub mod fisheye_camera;
+pub mod orthographic_camera;
+pub mod perspective_camera;
+pub mod spherical_camera;
+
+pub use fisheye_camera::{FisheyeCamera, FisheyeProjection};
+pub use orthographic_camera::OrthographicCamera;
+pub use perspective_camera::PerspectiveCamera;
+pub use spherical_camera::SphericalCamera;
@@ -0,0 +1,119 @@
+use crate::{
+    camera::{look_at_basis, Shutter},
+    Camera, Ray, Vect,
+};
+
+/// Camera with an orthographic projection: all the rays are parallel, and
+/// the size of the objects does not depend on their distance to the camera.
+#[derive(Clone, Debug)]
+pub struct OrthographicCamera {
+    // Center of the image
+    pos: Vect,
+
+    // Orthonormal basis of the camera
+    right: Vect,
+    up: Vect,
+    forward: Vect,
+
+    // Size of a pixel, in world units
+    pixel_size: f64,
+
+    shutter: Shutter,
+
+    // Dimensions of the image
+    width: usize,
+    height: usize,
+}
+
+impl OrthographicCamera {
+    /// Creates a camera centered on eye and looking at target. The view
+    /// height is the height of the area covered by the image, in world units.
+    pub fn new(
+        eye: Vect,
+        target: Vect,
+        up: Vect,
+        view_height: f64,
+        width: usize,
+        height: usize,
+    ) -> Box<Self> {
+        assert!(view_height > 0.);
+
+        let [right, up, forward] = look_at_basis(eye, target, up);
+
+        Box::new(Self {
+            pos: eye,
+            right,
+            up,
+            forward,
+            pixel_size: view_height / height as f64,
+            shutter: Shutter::default(),
+            width,
+            height,
+        })
+    }
+
+    /// Keeps the shutter open between two times, to get motion blur. By
+    /// default, the shutter opens and closes at time 0.
+    pub fn with_shutter(mut self: Box<Self>, open: f64, close: f64) -> Box<Self> {
+        self.shutter = Shutter::new(open, close);
+        self
+    }
+}
+
+impl Camera for OrthographicCamera {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn generate_ray(
+        &self,
+        x: f64,
+        y: f64,
+        _lens_sample: [f64; 2],
+        time_sample: f64,
+    ) -> Option<Ray> {
+        let origin = self.pos
+            + (self.pixel_size * (x - 0.5 * self.width as f64)) * self.right
+            + (self.pixel_size * (y - 0.5 * self.height as f64)) * self.up;
+
+        Some(Ray::new(origin, self.forward).with_time(self.shutter.time(time_sample)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_orthographic() {
+        let camera = OrthographicCamera::new(
+            Vect::zero(),
+            Vect::new(1., 0., 0.),
+            Vect::new(0., 0., 1.),
+            2.,
+            4,
+            2,
+        );
+
+        let center = camera.generate_ray(2., 1., [0., 0.], 0.).unwrap();
+        let corner = camera.generate_ray(0., 0., [0., 0.], 0.).unwrap();
+        let opposite = camera.generate_ray(4., 2., [0., 0.], 0.).unwrap();
+
+        // The rays are parallel
+        for ray in [center, corner, opposite] {
+            assert!((ray.dir.normalized() - Vect::new(1., 0., 0.)).norm() <= 1e-9);
+        }
+
+        // The origins cover the view, centered on the eye
+        assert!(center.pos.norm() <= 1e-9);
+        let offset = opposite.pos - corner.pos;
+        assert!(offset.x.abs() <= 1e-9);
+        assert!((offset.y.abs() - 4.).abs() <= 1e-9);
+        assert!((offset.z - 2.).abs() <= 1e-9);
+        assert!((corner.pos + opposite.pos).norm() <= 1e-9);
+    }
+}
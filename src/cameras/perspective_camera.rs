@@ -0,0 +1,194 @@
+use crate::{
+    camera::{look_at_basis, Shutter},
+    warping::{to_uniform_disk, to_uniform_polygon},
+    Camera, Ray, Vect,
+};
+
+/// Camera with a perspective projection, modelled either as a pinhole or as a
+/// thin lens
+#[derive(Clone, Debug)]
+pub struct PerspectiveCamera {
+    // Position of the focus point
+    pos: Vect,
+
+    // Orthonormal basis of the camera. The camera looks in the direction of
+    // forward, and right and up are the directions of the x and y axes of
+    // the image.
+    right: Vect,
+    up: Vect,
+    forward: Vect,
+
+    // Tangent of half the vertical field of view
+    tan_half_fov: f64,
+
+    // Thin lens model. With a radius of 0, the camera is a pinhole camera.
+    lens_radius: f64,
+    focus_distance: f64,
+
+    // Shape of the aperture: a disk if there are less than 3 blades, and a
+    // regular polygon otherwise
+    aperture_blades: usize,
+    aperture_rotation: f64,
+
+    shutter: Shutter,
+
+    // Dimensions of the image
+    width: usize,
+    height: usize,
+}
+
+impl PerspectiveCamera {
+    /// Creates a camera placed at eye and looking at target. The up vector
+    /// gives the vertical direction of the image, and does not need to be
+    /// orthogonal to the direction of the camera: tilting it rolls the
+    /// camera. The vertical field of view is given in degrees.
+    pub fn new(
+        eye: Vect,
+        target: Vect,
+        up: Vect,
+        vertical_fov: f64,
+        width: usize,
+        height: usize,
+    ) -> Box<Self> {
+        assert!(vertical_fov > 0. && vertical_fov < 180.);
+
+        let [right, up, forward] = look_at_basis(eye, target, up);
+
+        Box::new(Self {
+            pos: eye,
+            right,
+            up,
+            forward,
+            tan_half_fov: (0.5 * vertical_fov.to_radians()).tan(),
+            lens_radius: 0.,
+            focus_distance: 1.,
+            aperture_blades: 0,
+            aperture_rotation: 0.,
+            shutter: Shutter::default(),
+            width,
+            height,
+        })
+    }
+
+    /// Uses a thin lens instead of a pinhole, to get depth of field. Only the
+    /// points at the focus distance from the camera are sharp.
+    pub fn with_thin_lens(
+        mut self: Box<Self>,
+        aperture_radius: f64,
+        focus_distance: f64,
+    ) -> Box<Self> {
+        assert!(aperture_radius >= 0. && focus_distance > 0.);
+
+        self.lens_radius = aperture_radius;
+        self.focus_distance = focus_distance;
+        self
+    }
+
+    /// Uses a regular polygon with a given number of blades as the shape of
+    /// the aperture, which is visible in the out of focus highlights. The
+    /// rotation of the polygon is given in degrees.
+    pub fn with_polygonal_aperture(mut self: Box<Self>, blades: usize, rotation: f64) -> Box<Self> {
+        assert!(blades >= 3);
+
+        self.aperture_blades = blades;
+        self.aperture_rotation = rotation.to_radians();
+        self
+    }
+
+    /// Keeps the shutter open between two times, to get motion blur. By
+    /// default, the shutter opens and closes at time 0.
+    pub fn with_shutter(mut self: Box<Self>, open: f64, close: f64) -> Box<Self> {
+        self.shutter = Shutter::new(open, close);
+        self
+    }
+}
+
+impl Camera for PerspectiveCamera {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn generate_ray(&self, x: f64, y: f64, lens_sample: [f64; 2], time_sample: f64) -> Option<Ray> {
+        let half_height = 0.5 * self.height as f64;
+        let scale = self.tan_half_fov / half_height;
+
+        let dir = self.forward
+            + (scale * (x - 0.5 * self.width as f64)) * self.right
+            + (scale * (y - half_height)) * self.up;
+
+        if self.lens_radius == 0. {
+            return Some(Ray::new(self.pos, dir).with_time(self.shutter.time(time_sample)));
+        }
+
+        // All the rays going through the lens converge on the focus plane
+        let focus_point = self.pos + self.focus_distance * dir;
+
+        let on_lens = if self.aperture_blades >= 3 {
+            to_uniform_polygon(lens_sample, self.aperture_blades, self.aperture_rotation)
+        } else {
+            to_uniform_disk(lens_sample)
+        };
+        let origin = self.pos + self.lens_radius * (on_lens.x * self.right + on_lens.y * self.up);
+
+        Some(Ray::new(origin, focus_point - origin).with_time(self.shutter.time(time_sample)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_camera_orientations() {
+        let up = Vect::new(0., 0., 1.);
+
+        // Looking straight down
+        let camera = PerspectiveCamera::new(Vect::new(0., 0., 5.), Vect::zero(), up, 90., 200, 100);
+        let ray = camera.generate_ray(100., 50., [0., 0.], 0.).unwrap();
+        assert!((ray.dir - Vect::new(0., 0., -1.)).norm() <= 1e-9);
+
+        // The top of the image is at half the field of view
+        let camera = PerspectiveCamera::new(Vect::zero(), Vect::new(1., 0., 0.), up, 90., 200, 100);
+        let ray = camera.generate_ray(100., 100., [0., 0.], 0.).unwrap();
+        assert!((ray.dir - Vect::new(1., 0., 1.).normalized()).norm() <= 1e-9);
+        let ray = camera.generate_ray(200., 50., [0., 0.], 0.).unwrap();
+        assert!((ray.dir - Vect::new(1., -2., 0.).normalized()).norm() <= 1e-9);
+
+        // Rolled camera
+        let camera =
+            PerspectiveCamera::new(Vect::zero(), Vect::new(1., 0., 0.), -up, 90., 200, 100);
+        let ray = camera.generate_ray(100., 100., [0., 0.], 0.).unwrap();
+        assert!((ray.dir - Vect::new(1., 0., -1.).normalized()).norm() <= 1e-9);
+    }
+
+    #[test]
+    fn test_thin_lens() {
+        let camera = PerspectiveCamera::new(
+            Vect::zero(),
+            Vect::new(1., 0., 0.),
+            Vect::new(0., 0., 1.),
+            60.,
+            100,
+            100,
+        )
+        .with_thin_lens(0.5, 4.)
+        .with_polygonal_aperture(6, 10.);
+
+        // Every ray through a pixel converges at the focus distance
+        for sample in [[0.1, 0.7], [0.5, 0.5], [0.9, 0.2]] {
+            let ray = camera.generate_ray(30., 60., sample, 0.).unwrap();
+            let expected = camera.generate_ray(30., 60., [0., 0.], 0.).unwrap();
+
+            let t = (4. - ray.pos.x) / ray.dir.x;
+            let focus_point = ray.pos_in(t);
+            let t = 4. / expected.dir.x;
+            assert!((focus_point - expected.pos_in(t)).norm() <= 1e-9);
+
+            assert!(ray.pos.x == 0. && ray.pos.norm() <= 0.5);
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use std::f64::consts::PI;
+
+use crate::{
+    camera::{look_at_basis, Shutter},
+    Camera, Ray, Vect,
+};
+
+/// Camera seeing in every direction, with an equirectangular projection: the
+/// x axis of the image is the longitude, and the y axis the latitude. It can
+/// be used to render panoramas and environment maps.
+#[derive(Clone, Debug)]
+pub struct SphericalCamera {
+    pos: Vect,
+
+    // Orthonormal basis of the camera. The center of the image is in the
+    // direction of forward, and the poles in the direction of up.
+    right: Vect,
+    up: Vect,
+    forward: Vect,
+
+    shutter: Shutter,
+
+    // Dimensions of the image
+    width: usize,
+    height: usize,
+}
+
+impl SphericalCamera {
+    /// Creates a camera placed at eye, with target at the center of the image
+    pub fn new(eye: Vect, target: Vect, up: Vect, width: usize, height: usize) -> Box<Self> {
+        let [right, up, forward] = look_at_basis(eye, target, up);
+
+        Box::new(Self {
+            pos: eye,
+            right,
+            up,
+            forward,
+            shutter: Shutter::default(),
+            width,
+            height,
+        })
+    }
+
+    /// Keeps the shutter open between two times, to get motion blur. By
+    /// default, the shutter opens and closes at time 0.
+    pub fn with_shutter(mut self: Box<Self>, open: f64, close: f64) -> Box<Self> {
+        self.shutter = Shutter::new(open, close);
+        self
+    }
+}
+
+impl Camera for SphericalCamera {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn generate_ray(
+        &self,
+        x: f64,
+        y: f64,
+        _lens_sample: [f64; 2],
+        time_sample: f64,
+    ) -> Option<Ray> {
+        let longitude = (x / self.width as f64 - 0.5) * 2. * PI;
+        let latitude = (y / self.height as f64 - 0.5) * PI;
+
+        let dir = (latitude.cos() * longitude.cos()) * self.forward
+            + (latitude.cos() * longitude.sin()) * self.right
+            + latitude.sin() * self.up;
+
+        Some(Ray::new(self.pos, dir).with_time(self.shutter.time(time_sample)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spherical() {
+        let camera = SphericalCamera::new(
+            Vect::zero(),
+            Vect::new(1., 0., 0.),
+            Vect::new(0., 0., 1.),
+            200,
+            100,
+        );
+
+        let direction = |x, y| {
+            let ray = camera.generate_ray(x, y, [0., 0.], 0.).unwrap();
+            ray.dir.normalized()
+        };
+
+        // The center looks forward, and the top and bottom rows at the poles
+        assert!((direction(100., 50.) - Vect::new(1., 0., 0.)).norm() <= 1e-9);
+        for x in [0., 50., 150.] {
+            assert!((direction(x, 100.) - Vect::new(0., 0., 1.)).norm() <= 1e-9);
+            assert!((direction(x, 0.) - Vect::new(0., 0., -1.)).norm() <= 1e-9);
+        }
+
+        // The left and right borders look backward
+        assert!((direction(0., 50.) - Vect::new(-1., 0., 0.)).norm() <= 1e-9);
+    }
+}
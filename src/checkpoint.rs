@@ -0,0 +1,326 @@
+//! Binary checkpoints of progressive renders, from which a render can be
+//! resumed to add more samples.
+//!
+//! All the values are stored in little endian, the floats in double precision
+//! so that a resumed render gives exactly the same sums as an uninterrupted
+//! one.
+
+use crate::{Color, Film};
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+};
+
+const MAGIC: &[u8; 4] = b"RCKP";
+const VERSION: u32 = 2;
+
+/// Reason why a checkpoint cannot be loaded
+#[derive(Debug)]
+pub enum CheckpointError {
+    /// The file cannot be read, or is truncated
+    Io(io::Error),
+
+    /// The file is not a checkpoint
+    BadMagic,
+
+    /// The checkpoint was written by another version of the format
+    BadVersion(u32),
+
+    /// The checkpoint is for an image of another size, given as
+    /// (width, height)
+    SizeMismatch {
+        expected: (usize, usize),
+        found: (usize, usize),
+    },
+
+    /// The checkpoint does not record the same AOVs as the renderer
+    AovMismatch,
+
+    /// The checkpoint was saved by a renderer with another sampler, filter or
+    /// number of samples per step
+    SettingsMismatch,
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CheckpointError::Io(error) => write!(f, "cannot read the checkpoint: {error}"),
+            CheckpointError::BadMagic => write!(f, "not a checkpoint"),
+            CheckpointError::BadVersion(version) => {
+                write!(f, "unsupported checkpoint version {version}")
+            }
+            CheckpointError::SizeMismatch { expected, found } => write!(
+                f,
+                "checkpoint of a {}x{} image instead of {}x{}",
+                found.0, found.1, expected.0, expected.1
+            ),
+            CheckpointError::AovMismatch => write!(f, "checkpoint with other AOVs"),
+            CheckpointError::SettingsMismatch => {
+                write!(f, "checkpoint of a renderer with other settings")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CheckpointError {}
+
+impl From<io::Error> for CheckpointError {
+    fn from(error: io::Error) -> Self {
+        CheckpointError::Io(error)
+    }
+}
+
+/// Running mean and variance of the luminance of the samples of a pixel,
+/// computed with the algorithm of Welford
+#[derive(Clone, Default)]
+pub(crate) struct PixelStatistics {
+    pub count: usize,
+    pub mean: f64,
+
+    // Sum of the squared differences to the mean
+    pub m2: f64,
+}
+
+impl PixelStatistics {
+    pub fn add(&mut self, value: f64) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    /// Returns the standard error of the mean, relative to the mean
+    pub fn relative_error(&self) -> f64 {
+        if self.count < 2 {
+            return f64::INFINITY;
+        }
+
+        let variance = self.m2 / (self.count - 1) as f64;
+        (variance / self.count as f64).sqrt() / self.mean.abs().max(1e-3)
+    }
+}
+
+/// State of a progressive render after some steps. The samplers only depend
+/// on their seed, on the pixel and on the index of the sample, so their state
+/// is the number of samples of each pixel, which is the index of its next
+/// sample.
+pub(crate) struct Checkpoint {
+    /// Depends on the settings of the renderer which change the samples,
+    /// which must be the same to resume the render
+    pub fingerprint: u64,
+
+    pub steps: usize,
+    pub film: Film,
+    pub statistics: Vec<PixelStatistics>,
+    pub converged: Vec<bool>,
+}
+
+impl Checkpoint {
+    /// Creates the state of a render which has not started
+    pub fn new(film: Film, fingerprint: u64) -> Self {
+        let size = film.width() * film.height();
+
+        Self {
+            fingerprint,
+            steps: 0,
+            film,
+            statistics: vec![PixelStatistics::default(); size],
+            converged: vec![false; size],
+        }
+    }
+
+    /// Saves the checkpoint. It is first written to another file, so that the
+    /// previous checkpoint is kept if the program stops while writing.
+    pub fn save(&self, filename: &str) -> io::Result<()> {
+        let temporary = format!("{filename}.tmp");
+        let mut writer = BufWriter::new(File::create(&temporary)?);
+
+        writer.write_all(MAGIC)?;
+        write_u32(&mut writer, VERSION)?;
+        write_u64(&mut writer, self.fingerprint)?;
+        write_u64(&mut writer, self.steps as u64)?;
+        self.film.write_state(&mut writer)?;
+
+        for (statistics, &converged) in self.statistics.iter().zip(&self.converged) {
+            write_u64(&mut writer, statistics.count as u64)?;
+            write_f64(&mut writer, statistics.mean)?;
+            write_f64(&mut writer, statistics.m2)?;
+            write_u8(&mut writer, converged as u8)?;
+        }
+
+        writer.into_inner()?.sync_all()?;
+        fs::rename(&temporary, filename)
+    }
+
+    /// Loads a checkpoint into an empty film, which gives the expected size,
+    /// AOVs and filter. The filter is not saved in the checkpoint, only in the
+    /// fingerprint of the settings, which must be the expected one.
+    pub fn load(filename: &str, mut film: Film, fingerprint: u64) -> Result<Self, CheckpointError> {
+        let mut reader = BufReader::new(File::open(filename)?);
+
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(CheckpointError::BadMagic);
+        }
+        let version = read_u32(&mut reader)?;
+        if version != VERSION {
+            return Err(CheckpointError::BadVersion(version));
+        }
+        if read_u64(&mut reader)? != fingerprint {
+            return Err(CheckpointError::SettingsMismatch);
+        }
+
+        let steps = read_u64(&mut reader)? as usize;
+        film.read_state(&mut reader)?;
+
+        let size = film.width() * film.height();
+        let mut statistics = Vec::with_capacity(size);
+        let mut converged = Vec::with_capacity(size);
+        for _ in 0..size {
+            statistics.push(PixelStatistics {
+                count: read_u64(&mut reader)? as usize,
+                mean: read_f64(&mut reader)?,
+                m2: read_f64(&mut reader)?,
+            });
+            converged.push(read_u8(&mut reader)? != 0);
+        }
+
+        Ok(Self {
+            fingerprint,
+            steps,
+            film,
+            statistics,
+            converged,
+        })
+    }
+}
+
+pub(crate) fn write_u8(writer: &mut impl Write, value: u8) -> io::Result<()> {
+    writer.write_all(&[value])
+}
+
+pub(crate) fn write_u32(writer: &mut impl Write, value: u32) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+pub(crate) fn write_u64(writer: &mut impl Write, value: u64) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+pub(crate) fn write_f64(writer: &mut impl Write, value: f64) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+pub(crate) fn write_color(writer: &mut impl Write, color: Color) -> io::Result<()> {
+    for value in [color.red, color.green, color.blue] {
+        write_f64(writer, value)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn read_u8(reader: &mut impl Read) -> io::Result<u8> {
+    let mut bytes = [0; 1];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+pub(crate) fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+pub(crate) fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+pub(crate) fn read_f64(reader: &mut impl Read) -> io::Result<f64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(f64::from_le_bytes(bytes))
+}
+
+pub(crate) fn read_color(reader: &mut impl Read) -> io::Result<Color> {
+    let red = read_f64(reader)?;
+    let green = read_f64(reader)?;
+    let blue = read_f64(reader)?;
+    Ok(Color::new(red, green, blue))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filters::BoxFilter;
+    use crate::Aov;
+
+    #[test]
+    fn test_load_rejects_invalid_files() {
+        let film = |width, height, aovs: &[Aov]| {
+            Film::new(width, height, BoxFilter::new(0.5)).with_aovs(aovs)
+        };
+        let path = std::env::temp_dir().join("renderer-test-invalid-checkpoint.bin");
+        let filename = path.to_str().unwrap();
+
+        Checkpoint::new(film(4, 3, &[Aov::Albedo]), 7)
+            .save(filename)
+            .unwrap();
+        let bytes = fs::read(filename).unwrap();
+        let load_modified = |modify: &dyn Fn(&mut Vec<u8>), expected: Film| {
+            let mut modified = bytes.clone();
+            modify(&mut modified);
+            fs::write(filename, modified).unwrap();
+            Checkpoint::load(filename, expected, 7)
+        };
+
+        // The valid file loads, into a film of the same size and AOVs only
+        assert!(load_modified(&|_| (), film(4, 3, &[Aov::Albedo])).is_ok());
+        assert!(matches!(
+            load_modified(&|_| (), film(3, 4, &[Aov::Albedo])),
+            Err(CheckpointError::SizeMismatch {
+                expected: (3, 4),
+                found: (4, 3)
+            })
+        ));
+        assert!(matches!(
+            load_modified(&|_| (), film(4, 3, &[Aov::Normal])),
+            Err(CheckpointError::AovMismatch)
+        ));
+
+        // Truncated, foreign and newer files
+        let truncate = |bytes: &mut Vec<u8>| bytes.truncate(bytes.len() - 1);
+        assert!(matches!(
+            load_modified(&truncate, film(4, 3, &[Aov::Albedo])),
+            Err(CheckpointError::Io(_))
+        ));
+        assert!(matches!(
+            load_modified(&|bytes| bytes[0] = b'P', film(4, 3, &[Aov::Albedo])),
+            Err(CheckpointError::BadMagic)
+        ));
+        assert!(matches!(
+            load_modified(&|bytes| bytes[4] = 3, film(4, 3, &[Aov::Albedo])),
+            Err(CheckpointError::BadVersion(3))
+        ));
+
+        // A checkpoint saved with other settings, such as another seed
+        assert!(matches!(
+            load_modified(&|bytes| bytes[8] = 8, film(4, 3, &[Aov::Albedo])),
+            Err(CheckpointError::SettingsMismatch)
+        ));
+
+        // A checkpoint cannot be saved in a missing folder
+        let missing = std::env::temp_dir().join("renderer-missing-folder/checkpoint.bin");
+        assert!(Checkpoint::new(film(4, 3, &[]), 7)
+            .save(missing.to_str().unwrap())
+            .is_err());
+
+        fs::remove_file(filename).unwrap();
+        assert!(matches!(
+            Checkpoint::load(filename, film(4, 3, &[Aov::Albedo]), 7),
+            Err(CheckpointError::Io(_))
+        ));
+    }
+}
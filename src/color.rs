@@ -1,3 +1,5 @@
+use std::ops::{Add, AddAssign, Mul};
+
 #[derive(Copy, Clone, Debug)]
 pub struct Color {
     pub red: f64,
@@ -29,4 +31,51 @@ impl Color {
     pub fn blue() -> Self {
         Self::new(0., 0., 1.)
     }
+
+    /// Returns the luminance of the color, with the weights of the sRGB
+    /// primaries
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.red + 0.7152 * self.green + 0.0722 * self.blue
+    }
+}
+
+impl Add for Color {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::new(
+            self.red + rhs.red,
+            self.green + rhs.green,
+            self.blue + rhs.blue,
+        )
+    }
+}
+
+impl AddAssign for Color {
+    fn add_assign(&mut self, rhs: Self) {
+        self.red += rhs.red;
+        self.green += rhs.green;
+        self.blue += rhs.blue;
+    }
+}
+
+impl Mul<Color> for f64 {
+    type Output = Color;
+
+    fn mul(self, rhs: Color) -> Color {
+        Color::new(self * rhs.red, self * rhs.green, self * rhs.blue)
+    }
+}
+
+/// Component-wise product, for instance to filter a light by a material
+impl Mul for Color {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self::new(
+            self.red * rhs.red,
+            self.green * rhs.green,
+            self.blue * rhs.blue,
+        )
+    }
 }
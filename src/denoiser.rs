@@ -0,0 +1,313 @@
+use crate::{Aov, Color, Film, Image};
+
+/// Edge-avoiding denoiser, computing each pixel as a weighted average of its
+/// neighbours (joint bilateral filter). The weights decrease with the
+/// distance between the pixels, the difference between their colors relative
+/// to their variance, and the differences between their features: albedo,
+/// normal and depth. The features of the film which are not recorded are not
+/// used.
+///
+/// The albedo is divided out before filtering and multiplied back after it,
+/// so that the textures are kept sharp. The channels whose albedo is almost
+/// zero, for instance on the lights, are filtered as they are.
+#[derive(Clone, Debug)]
+pub struct Denoiser {
+    radius: usize,
+    sigma_spatial: f64,
+
+    // Larger values give smoother images, with more bias
+    color_sensitivity: f64,
+
+    sigma_albedo: f64,
+    sigma_normal: f64,
+
+    // Relative to the depth of the pixel
+    sigma_depth: f64,
+}
+
+/// Features of a pixel guiding the filter
+struct Pixel {
+    color: Color,
+
+    // Albedo divided out of the color, 1 for the channels without albedo
+    modulation: Color,
+
+    variance: Option<Color>,
+    albedo: Option<Color>,
+    normal: Option<Color>,
+    depth: Option<f64>,
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Self {
+            radius: 6,
+            sigma_spatial: 3.,
+            color_sensitivity: 1.,
+            sigma_albedo: 0.05,
+            sigma_normal: 0.2,
+            sigma_depth: 0.05,
+        }
+    }
+}
+
+impl Denoiser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Averages the pixels within a given distance, weighted by a Gaussian of
+    /// standard deviation sigma
+    pub fn with_window(mut self, radius: usize, sigma: f64) -> Self {
+        self.radius = radius;
+        self.sigma_spatial = sigma;
+        self
+    }
+
+    /// Tolerance on the color differences, in standard deviations of the
+    /// noise. It is only used when the variance is recorded by the film.
+    pub fn with_color_sensitivity(mut self, sensitivity: f64) -> Self {
+        self.color_sensitivity = sensitivity;
+        self
+    }
+
+    /// Returns the AOVs used by the denoiser
+    pub fn aovs() -> [Aov; 4] {
+        [Aov::Albedo, Aov::Normal, Aov::Depth, Aov::Variance]
+    }
+
+    /// Returns the denoised image of a film
+    pub fn denoise(&self, film: &Film) -> Image {
+        let (width, height) = (film.width(), film.height());
+        let image = film.image();
+        let aov = |aov| film.aovs().contains(&aov).then(|| film.aov_image(aov));
+        let (variance, albedo) = (aov(Aov::Variance), aov(Aov::Albedo));
+        let (normal, depth) = (aov(Aov::Normal), aov(Aov::Depth));
+
+        let pixels: Vec<Pixel> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let albedo = albedo.as_ref().map(|image| image.pixel(x, y));
+                let modulation = albedo.map_or(Color::white(), |albedo| {
+                    let factor = |c: f64| if c > 1e-3 { c } else { 1. };
+                    Color::new(
+                        factor(albedo.red),
+                        factor(albedo.green),
+                        factor(albedo.blue),
+                    )
+                });
+                let inv_albedo = Color::new(
+                    1. / modulation.red,
+                    1. / modulation.green,
+                    1. / modulation.blue,
+                );
+
+                Pixel {
+                    color: inv_albedo * image.pixel(x, y),
+                    modulation,
+                    variance: variance
+                        .as_ref()
+                        .map(|image| inv_albedo * inv_albedo * image.pixel(x, y)),
+                    albedo,
+                    normal: normal.as_ref().map(|image| image.pixel(x, y)),
+                    depth: depth.as_ref().map(|image| image.pixel(x, y).red),
+                }
+            })
+            .collect();
+
+        let mut denoised = Image::new(width, height);
+
+        for y in 0..height {
+            for x in 0..width {
+                let p = &pixels[y * width + x];
+
+                let mut sum = Color::black();
+                let mut weights = 0.;
+
+                let r = self.radius as isize;
+                for dy in -r..=r {
+                    for dx in -r..=r {
+                        let (qx, qy) = (x as isize + dx, y as isize + dy);
+                        if qx < 0 || qy < 0 || qx >= width as isize || qy >= height as isize {
+                            continue;
+                        }
+                        let q = &pixels[qy as usize * width + qx as usize];
+
+                        let distance = (dx * dx + dy * dy) as f64;
+                        let weight = (-distance / (2. * self.sigma_spatial.powi(2))
+                            - self.color_distance(&pixels, width, height, (x, y), (dx, dy))
+                            - self.feature_distance(p, q))
+                        .exp();
+
+                        sum += weight * q.color;
+                        weights += weight;
+                    }
+                }
+
+                denoised.set_pixel(x, y, p.modulation * ((1. / weights) * sum));
+            }
+        }
+
+        denoised
+    }
+
+    /// Returns the opposite of the logarithm of the weight given by the
+    /// difference between the colors of a pixel and of its neighbour at a given
+    /// offset, relative to the noise. As in the non-local means of Rousselle et
+    /// al. (2012), the differences are averaged over the 3x3 patches around
+    /// the pixels, which makes them much less noisy.
+    fn color_distance(
+        &self,
+        pixels: &[Pixel],
+        width: usize,
+        height: usize,
+        (x, y): (usize, usize),
+        (dx, dy): (isize, isize),
+    ) -> f64 {
+        let k2 = self.color_sensitivity.powi(2);
+        let channel = |cp: f64, cq: f64, vp: f64, vq: f64| {
+            ((cp - cq).powi(2) - (vp + vp.min(vq))) / (1e-4 + k2 * (vp + vq))
+        };
+
+        let mut distance = 0.;
+        let mut count = 0;
+
+        for py in y.saturating_sub(1)..(y + 2).min(height) {
+            for px in x.saturating_sub(1)..(x + 2).min(width) {
+                let (qx, qy) = (px as isize + dx, py as isize + dy);
+                if qx < 0 || qy < 0 || qx >= width as isize || qy >= height as isize {
+                    continue;
+                }
+
+                let p = &pixels[py * width + px];
+                let q = &pixels[qy as usize * width + qx as usize];
+                if let (Some(vp), Some(vq)) = (p.variance, q.variance) {
+                    distance += channel(p.color.red, q.color.red, vp.red, vq.red)
+                        + channel(p.color.green, q.color.green, vp.green, vq.green)
+                        + channel(p.color.blue, q.color.blue, vp.blue, vq.blue);
+                    count += 3;
+                }
+            }
+        }
+
+        if count == 0 {
+            0.
+        } else {
+            (distance / count as f64).max(0.)
+        }
+    }
+
+    /// Returns the opposite of the logarithm of the weight given by the
+    /// differences between the features of two pixels
+    fn feature_distance(&self, p: &Pixel, q: &Pixel) -> f64 {
+        let squared = |a: Color, b: Color| {
+            (a.red - b.red).powi(2) + (a.green - b.green).powi(2) + (a.blue - b.blue).powi(2)
+        };
+
+        let mut distance = 0.;
+
+        if let (Some(ap), Some(aq)) = (p.albedo, q.albedo) {
+            distance += squared(ap, aq) / (2. * self.sigma_albedo.powi(2));
+        }
+
+        if let (Some(np), Some(nq)) = (p.normal, q.normal) {
+            distance += squared(np, nq) / (2. * self.sigma_normal.powi(2));
+        }
+
+        if let (Some(dp), Some(dq)) = (p.depth, q.depth) {
+            if dp.is_finite() != dq.is_finite() {
+                return f64::INFINITY;
+            }
+            if dp.is_finite() {
+                let scale = self.sigma_depth * dp.abs().max(1e-3);
+                distance += (dp - dq).powi(2) / (2. * scale * scale);
+            }
+        }
+
+        distance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::film::FirstHit;
+    use crate::filters::BoxFilter;
+    use crate::Vect;
+
+    #[test]
+    fn test_denoise() {
+        let (width, height) = (32, 16);
+        let mut film = Film::new(width, height, BoxFilter::new(0.5)).with_aovs(&Denoiser::aovs());
+        let mut rng = fastrand::Rng::with_seed(4);
+
+        // Two walls with different albedos and noisy lighting
+        let albedo = |x: usize| if x < width / 2 { 0.2 } else { 0.8 };
+        for y in 0..height {
+            for x in 0..width {
+                let hit = FirstHit {
+                    albedo: Color::new(albedo(x), albedo(x), albedo(x)),
+                    normal: Vect::new(0., 0., 1.),
+                    depth: 2.,
+                    position: Vect::zero(),
+                    primitive: (x >= width / 2) as usize,
+                };
+
+                for _ in 0..4 {
+                    let (sx, sy) = (x as f64 + rng.f64(), y as f64 + rng.f64());
+                    let light = 2. * rng.f64();
+                    film.add_sample_with_hit(sx, sy, light * hit.albedo, Some(hit));
+                }
+            }
+        }
+
+        let error = |image: &Image| {
+            let mut error = 0.;
+            for y in 0..height {
+                for x in 0..width {
+                    error += (image.pixel(x, y).green - albedo(x)).powi(2);
+                }
+            }
+            error
+        };
+
+        let denoised = Denoiser::new().denoise(&film);
+        assert!(error(&denoised) < 0.2 * error(&film.image()));
+
+        // The edge is not blurred: the columns on each side keep their mean
+        let mean = |x| (0..height).map(|y| denoised.pixel(x, y).red).sum::<f64>() / height as f64;
+        assert!((mean(width / 2 - 1) - 0.2).abs() < 0.03);
+        assert!((mean(width / 2) - 0.8).abs() < 0.06);
+
+        // A white light, whose albedo is the magenta of its material, and a
+        // black surface keep their colors
+        let mut film = Film::new(8, 8, BoxFilter::new(0.5)).with_aovs(&Denoiser::aovs());
+        for y in 0..8 {
+            for x in 0..8 {
+                let light = x < 4;
+                let hit = FirstHit {
+                    albedo: if light {
+                        Color::new(1., 0., 1.)
+                    } else {
+                        Color::black()
+                    },
+                    normal: Vect::new(0., 0., 1.),
+                    depth: 2.,
+                    position: Vect::zero(),
+                    primitive: light as usize,
+                };
+                let color = if light {
+                    Color::white()
+                } else {
+                    Color::new(0.3, 0.3, 0.3)
+                };
+                film.add_sample_with_hit(x as f64 + 0.5, y as f64 + 0.5, color, Some(hit));
+            }
+        }
+
+        let denoised = Denoiser::new().denoise(&film);
+        let (light, black) = (denoised.pixel(1, 4), denoised.pixel(6, 4));
+        assert!((light.green - 1.).abs() < 1e-9 && (light.blue - 1.).abs() < 1e-9);
+        assert!((black.red - 0.3).abs() < 1e-9);
+    }
+}
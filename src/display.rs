@@ -0,0 +1,266 @@
+use crate::{blue_noise::blue_noise, Color, Image};
+
+/// Curve compressing the radiance of the pixels into [0; 1]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToneMapper {
+    /// The values above 1 are clipped
+    Clamp,
+
+    /// x / (1 + x) on each component
+    Reinhard,
+
+    /// Fit of the ACES filmic curve by Narkowicz (2015)
+    AcesFilmic,
+
+    /// Minimal version of AgX, by Wrensch (2023). The saturated highlights
+    /// are desaturated towards white instead of being clipped.
+    AgX,
+
+    /// Filmic curve of Uncharted 2, by Hable (2010)
+    Hable,
+}
+
+/// Noise added before the quantisation to 8 bits, to hide the banding of the
+/// smooth gradients
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dithering {
+    /// The values are rounded to the nearest level
+    None,
+
+    /// 8x8 Bayer matrix
+    Ordered,
+
+    /// Blue-noise tile, which has no visible pattern
+    BlueNoise,
+}
+
+/// Converts the linear radiance of the pixels to 8-bit sRGB values: the
+/// exposure is applied, the colors are tone mapped and encoded with the sRGB
+/// transfer function, then dithered and quantised.
+#[derive(Clone, Debug)]
+pub struct DisplayPipeline {
+    exposure: f64,
+    tone_mapper: ToneMapper,
+    dithering: Dithering,
+}
+
+impl Default for DisplayPipeline {
+    fn default() -> Self {
+        Self {
+            exposure: 0.,
+            tone_mapper: ToneMapper::Clamp,
+            dithering: Dithering::None,
+        }
+    }
+}
+
+impl DisplayPipeline {
+    /// Creates a pipeline clipping the values above 1, without dithering
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Multiplies the radiance by 2^stops before the tone mapping
+    pub fn with_exposure(mut self, stops: f64) -> Self {
+        self.exposure = stops;
+        self
+    }
+
+    pub fn with_tone_mapper(mut self, tone_mapper: ToneMapper) -> Self {
+        self.tone_mapper = tone_mapper;
+        self
+    }
+
+    pub fn with_dithering(mut self, dithering: Dithering) -> Self {
+        self.dithering = dithering;
+        self
+    }
+
+    /// Returns the linear color in [0; 1] displayed for a radiance
+    pub fn tone_map(&self, color: Color) -> Color {
+        let scale = self.exposure.exp2();
+        let [red, green, blue] = [color.red, color.green, color.blue].map(|c| (scale * c).max(0.));
+
+        let [red, green, blue] = match self.tone_mapper {
+            ToneMapper::Clamp => [red, green, blue],
+            ToneMapper::Reinhard => [red, green, blue].map(|c| c / (1. + c)),
+            ToneMapper::AcesFilmic => {
+                [red, green, blue].map(|c| (c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14))
+            }
+            ToneMapper::AgX => agx([red, green, blue]),
+            ToneMapper::Hable => {
+                // Linear white point, mapped to 1
+                const WHITE: f64 = 11.2;
+                [red, green, blue].map(|c| hable(2. * c) / hable(WHITE))
+            }
+        };
+
+        Color::new(red.clamp(0., 1.), green.clamp(0., 1.), blue.clamp(0., 1.))
+    }
+
+    /// Returns the 8-bit sRGB value of a pixel of radiance color. The position
+    /// of the pixel is used by the dithering.
+    pub fn to_srgb8(&self, color: Color, x: usize, y: usize) -> [u8; 3] {
+        let color = self.tone_map(color);
+
+        // Threshold in [0; 1[ of the quantisation
+        let threshold = match self.dithering {
+            Dithering::None => 0.5,
+            Dithering::Ordered => bayer(x % 8, y % 8, 3),
+            Dithering::BlueNoise => blue_noise(x, y),
+        };
+
+        [color.red, color.green, color.blue]
+            .map(|c| (255. * srgb_oetf(c) + threshold).floor().clamp(0., 255.) as u8)
+    }
+}
+
+/// Returns a false-color image of the red component of an image, for instance
+/// of the number of samples of the pixels: the values are mapped from blue for
+/// the lowest one to red for the highest one
+pub fn heatmap(image: &Image) -> Image {
+    let values = (0..image.width()).flat_map(|x| (0..image.height()).map(move |y| (x, y)));
+    let (min, max) = values
+        .clone()
+        .map(|(x, y)| image.pixel(x, y).red)
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+            (min.min(v), max.max(v))
+        });
+
+    // Blue, cyan, green, yellow and red
+    let ramp = [
+        Color::new(0., 0., 1.),
+        Color::new(0., 1., 1.),
+        Color::new(0., 1., 0.),
+        Color::new(1., 1., 0.),
+        Color::new(1., 0., 0.),
+    ];
+
+    let mut heatmap = Image::new(image.width(), image.height());
+    for (x, y) in values {
+        let t = if max > min {
+            (image.pixel(x, y).red - min) / (max - min)
+        } else {
+            0.
+        };
+
+        let position = t * (ramp.len() - 1) as f64;
+        let i = (position as usize).min(ramp.len() - 2);
+        let s = position - i as f64;
+        heatmap.set_pixel(x, y, (1. - s) * ramp[i] + s * ramp[i + 1]);
+    }
+
+    heatmap
+}
+
+/// sRGB transfer function, converting a linear value in [0; 1] to its encoded
+/// value
+pub fn srgb_oetf(value: f64) -> f64 {
+    if value <= 0.0031308 {
+        12.92 * value
+    } else {
+        1.055 * value.powf(1. / 2.4) - 0.055
+    }
+}
+
+/// Returns the threshold of a cell of the Bayer matrix of size 2^level,
+/// in [0; 1[
+fn bayer(x: usize, y: usize, level: u32) -> f64 {
+    // The lowest bits of the coordinates give the highest digits of the
+    // index, so that neighbouring cells have distant thresholds
+    let mut index = 0;
+    for bit in 0..level {
+        let (bx, by) = ((x >> bit) & 1, (y >> bit) & 1);
+        index = 4 * index + [[0, 2], [3, 1]][by][bx];
+    }
+
+    (index as f64 + 0.5) / (1 << (2 * level)) as f64
+}
+
+fn hable(x: f64) -> f64 {
+    let (a, b, c, d, e, f) = (0.15, 0.5, 0.1, 0.2, 0.02, 0.3);
+    (x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f) - e / f
+}
+
+/// Applies AgX to a linear color, and returns a linear color
+fn agx([red, green, blue]: [f64; 3]) -> [f64; 3] {
+    const MIN_EV: f64 = -12.47393;
+    const MAX_EV: f64 = 4.026069;
+
+    // Inset of the primaries, in log2 space
+    let inset = [
+        0.842479062253094 * red + 0.0784335999999992 * green + 0.0792237451477643 * blue,
+        0.0423282422610123 * red + 0.878468636469772 * green + 0.0791661274605434 * blue,
+        0.0423756549057051 * red + 0.0784336 * green + 0.879142973793104 * blue,
+    ]
+    .map(|c| {
+        let x = (c.max(1e-10).log2().clamp(MIN_EV, MAX_EV) - MIN_EV) / (MAX_EV - MIN_EV);
+
+        // Polynomial fit of the sigmoid
+        let (x2, x4) = (x * x, x * x * x * x);
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x
+            - 0.00232
+    });
+
+    // Outset of the primaries. The result is encoded with a gamma of 2.2.
+    let [r, g, b] = inset;
+    [
+        1.19687900512017 * r - 0.0980208811401368 * g - 0.0990297440797205 * b,
+        -0.0528968517574562 * r + 1.15190312990417 * g - 0.0989611768448433 * b,
+        -0.0529716355144438 * r - 0.0980434501171241 * g + 1.15107367264116 * b,
+    ]
+    .map(|c| c.max(0.).powf(2.2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_pipeline() {
+        let gray = |value| Color::new(value, value, value);
+
+        let pipeline = DisplayPipeline::new();
+        assert_eq!(pipeline.to_srgb8(gray(0.), 0, 0), [0; 3]);
+        assert_eq!(pipeline.to_srgb8(gray(0.216), 0, 0), [128; 3]);
+        assert_eq!(pipeline.to_srgb8(gray(1.), 0, 0), [255; 3]);
+        assert_eq!(pipeline.to_srgb8(gray(7.), 0, 0), [255; 3]);
+
+        // One stop less
+        let pipeline = pipeline.with_exposure(-1.);
+        assert_eq!(pipeline.to_srgb8(gray(0.432), 0, 0), [128; 3]);
+
+        // The tone mappers are increasing, and keep some contrast in the
+        // highlights
+        for tone_mapper in [
+            ToneMapper::Reinhard,
+            ToneMapper::AcesFilmic,
+            ToneMapper::AgX,
+            ToneMapper::Hable,
+        ] {
+            let pipeline = DisplayPipeline::new().with_tone_mapper(tone_mapper);
+            let values: Vec<f64> = [0.01, 0.1, 0.5, 1., 2., 4.]
+                .iter()
+                .map(|&v| pipeline.tone_map(gray(v)).green)
+                .collect();
+
+            assert!(values.windows(2).all(|w| w[0] < w[1]));
+            assert!(values[5] < 1.);
+        }
+
+        // The dithered values are right on average
+        for dithering in [Dithering::Ordered, Dithering::BlueNoise] {
+            let pipeline = DisplayPipeline::new().with_dithering(dithering);
+            let value: f64 = (100.25 / 255. + 0.055) / 1.055;
+            let linear = value.powf(2.4);
+
+            let mut sum = 0.;
+            for x in 0..64 {
+                for y in 0..64 {
+                    sum += pipeline.to_srgb8(gray(linear), x, y)[0] as f64;
+                }
+            }
+            assert!((sum / (64. * 64.) - 100.25).abs() < 0.02);
+        }
+    }
+}
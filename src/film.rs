@@ -0,0 +1,523 @@
+use crate::checkpoint::{
+    read_color, read_f64, read_u32, read_u64, read_u8, write_color, write_f64, write_u32,
+    write_u64, write_u8, CheckpointError,
+};
+use crate::{image::export_exr_layers, Color, Filter, Image, Vect};
+use std::io::{self, Read, Write};
+
+/// Auxiliary buffer which can be recorded by a film, in addition to the color
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Aov {
+    /// Color of the material of the first surface hit
+    Albedo,
+
+    /// Normal of the first surface hit
+    Normal,
+
+    /// Distance between the camera and the first surface hit, infinite if
+    /// there is none
+    Depth,
+
+    /// Position of the first surface hit, in world space
+    Position,
+
+    /// Index in the scene of the first primitive hit, -1 if there is none.
+    /// It is the primitive hit by the sample with the largest weight.
+    PrimitiveIndex,
+
+    /// Variance of the estimation of the color of the pixel
+    Variance,
+
+    /// Number of samples inside the pixel
+    SampleCount,
+}
+
+impl Aov {
+    /// All the AOVs, in the order of their codes in the checkpoints
+    const ALL: [Aov; 7] = [
+        Aov::Albedo,
+        Aov::Normal,
+        Aov::Depth,
+        Aov::Position,
+        Aov::PrimitiveIndex,
+        Aov::Variance,
+        Aov::SampleCount,
+    ];
+
+    /// Returns the name of the layer of the buffer
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aov::Albedo => "albedo",
+            Aov::Normal => "normal",
+            Aov::Depth => "depth",
+            Aov::Position => "position",
+            Aov::PrimitiveIndex => "primitive",
+            Aov::Variance => "variance",
+            Aov::SampleCount => "samples",
+        }
+    }
+}
+
+/// Information about the first surface hit by the camera ray of a sample
+#[derive(Clone, Copy, Debug)]
+pub struct FirstHit {
+    pub albedo: Color,
+    pub normal: Vect,
+    pub depth: f64,
+    pub position: Vect,
+    pub primitive: usize,
+}
+
+/// Weighted sums of the first hits of the samples of a pixel
+#[derive(Clone, Debug)]
+struct HitSums {
+    weight: f64,
+    albedo: Color,
+    normal: Vect,
+    depth: f64,
+    position: Vect,
+
+    // Primitive of the sample with the largest weight
+    primitive: Option<(f64, usize)>,
+}
+
+/// Accumulates the samples of an image. Each sample is added to all the pixels
+/// around it, weighted by a reconstruction filter, and the value of a pixel is
+/// the weighted average of its samples.
+#[derive(Clone, Debug)]
+pub struct Film {
+    width: usize,
+    height: usize,
+    filter: Box<dyn Filter>,
+
+    // Weighted sum of the samples and sum of the weights of each pixel, where
+    // the pixel (x, y) is at index y * width + x
+    sums: Vec<Color>,
+    weights: Vec<f64>,
+
+    // Only allocated if there are AOVs. The squared values are used to
+    // compute the variance.
+    aovs: Vec<Aov>,
+    hits: Vec<HitSums>,
+    squared_sums: Vec<Color>,
+    squared_weights: Vec<f64>,
+    sample_counts: Vec<u32>,
+}
+
+impl Film {
+    /// Creates an empty film
+    pub fn new(width: usize, height: usize, filter: Box<dyn Filter>) -> Self {
+        Self {
+            width,
+            height,
+            filter,
+            sums: vec![Color::black(); width * height],
+            weights: vec![0.; width * height],
+            aovs: Vec::new(),
+            hits: Vec::new(),
+            squared_sums: Vec::new(),
+            squared_weights: Vec::new(),
+            sample_counts: Vec::new(),
+        }
+    }
+
+    /// Records some AOVs in addition to the color
+    pub fn with_aovs(mut self, aovs: &[Aov]) -> Self {
+        let size = self.width * self.height;
+
+        self.aovs = aovs.to_vec();
+        if !aovs.is_empty() {
+            let hit = HitSums {
+                weight: 0.,
+                albedo: Color::black(),
+                normal: Vect::zero(),
+                depth: 0.,
+                position: Vect::zero(),
+                primitive: None,
+            };
+            self.hits = vec![hit; size];
+            self.squared_sums = vec![Color::black(); size];
+            self.squared_weights = vec![0.; size];
+            self.sample_counts = vec![0; size];
+        }
+
+        self
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the AOVs recorded by the film
+    pub fn aovs(&self) -> &[Aov] {
+        &self.aovs
+    }
+
+    /// Adds a sample at a position of the film, in pixels. The center of the
+    /// pixel (x, y) is at (x + 0.5, y + 0.5).
+    pub fn add_sample(&mut self, x: f64, y: f64, color: Color) {
+        self.add_sample_with_hit(x, y, color, None);
+    }
+
+    /// Adds a sample with the first surface it hit, which is used by the AOVs
+    pub fn add_sample_with_hit(&mut self, x: f64, y: f64, color: Color, hit: Option<FirstHit>) {
+        let radius = self.filter.radius();
+
+        // Pixels whose center is within the radius of the sample
+        let x_min = (x - 0.5 - radius).ceil().max(0.) as usize;
+        let x_max = (x - 0.5 + radius).floor().min(self.width as f64 - 1.);
+        let y_min = (y - 0.5 - radius).ceil().max(0.) as usize;
+        let y_max = (y - 0.5 + radius).floor().min(self.height as f64 - 1.);
+
+        if x_max < 0. || y_max < 0. {
+            return;
+        }
+
+        if !self.aovs.is_empty() && x >= 0. && y >= 0. {
+            let (px, py) = (x as usize, y as usize);
+            if px < self.width && py < self.height {
+                self.sample_counts[py * self.width + px] += 1;
+            }
+        }
+
+        for py in y_min..=y_max as usize {
+            for px in x_min..=x_max as usize {
+                let weight = self
+                    .filter
+                    .evaluate(x - (px as f64 + 0.5), y - (py as f64 + 0.5));
+
+                if weight != 0. {
+                    let index = py * self.width + px;
+                    self.sums[index] += weight * color;
+                    self.weights[index] += weight;
+
+                    if !self.aovs.is_empty() {
+                        self.add_aovs(index, weight, color, hit);
+                    }
+                }
+            }
+        }
+    }
+
+    fn add_aovs(&mut self, index: usize, weight: f64, color: Color, hit: Option<FirstHit>) {
+        self.squared_sums[index] += weight * (color * color);
+        self.squared_weights[index] += weight * weight;
+
+        if let Some(hit) = hit {
+            let sums = &mut self.hits[index];
+            sums.weight += weight;
+            sums.albedo += weight * hit.albedo;
+            sums.normal += weight * hit.normal;
+            sums.depth += weight * hit.depth;
+            sums.position += weight * hit.position;
+
+            if sums.primitive.is_none_or(|(best, _)| weight > best) {
+                sums.primitive = Some((weight, hit.primitive));
+            }
+        }
+    }
+
+    /// Returns the image developed from the samples. Pixels without any
+    /// sample are black.
+    pub fn image(&self) -> Image {
+        let mut image = Image::new(self.width, self.height);
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let index = y * self.width + x;
+                if self.weights[index] != 0. {
+                    image.set_pixel(x, y, (1. / self.weights[index]) * self.sums[index]);
+                }
+            }
+        }
+
+        image
+    }
+
+    /// Returns the image of an AOV recorded by the film. The vectors are
+    /// stored in the red, green and blue components, and the scalars in all
+    /// of them.
+    pub fn aov_image(&self, aov: Aov) -> Image {
+        assert!(self.aovs.contains(&aov), "AOV not recorded by the film");
+
+        let gray = |value| Color::new(value, value, value);
+        let from_vect = |v: Vect| Color::new(v.x, v.y, v.z);
+
+        let mut image = Image::new(self.width, self.height);
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let index = y * self.width + x;
+                let hit = &self.hits[index];
+                let inv_weight = 1. / hit.weight;
+
+                let color = if aov == Aov::Variance {
+                    self.variance(index)
+                } else if aov == Aov::SampleCount {
+                    gray(self.sample_counts[index] as f64)
+                } else if hit.weight == 0. {
+                    // No sample of the pixel hit a surface
+                    match aov {
+                        Aov::Depth => gray(f64::INFINITY),
+                        Aov::PrimitiveIndex => gray(-1.),
+                        _ => Color::black(),
+                    }
+                } else {
+                    match aov {
+                        Aov::Albedo => inv_weight * hit.albedo,
+                        Aov::Normal => from_vect(inv_weight * hit.normal),
+                        Aov::Depth => gray(inv_weight * hit.depth),
+                        Aov::Position => from_vect(inv_weight * hit.position),
+                        Aov::PrimitiveIndex => gray(hit.primitive.map_or(-1., |p| p.1 as f64)),
+                        Aov::Variance | Aov::SampleCount => unreachable!(),
+                    }
+                };
+
+                image.set_pixel(x, y, color);
+            }
+        }
+
+        image
+    }
+
+    /// Returns the variance of the weighted mean of the samples of a pixel
+    fn variance(&self, index: usize) -> Color {
+        let weight = self.weights[index];
+        if weight == 0. {
+            return Color::black();
+        }
+
+        let mean = (1. / weight) * self.sums[index];
+        let squared_mean = (1. / weight) * self.squared_sums[index];
+        let factor = self.squared_weights[index] / (weight * weight);
+
+        let variance = |squared: f64, mean: f64| factor * (squared - mean * mean).max(0.);
+        Color::new(
+            variance(squared_mean.red, mean.red),
+            variance(squared_mean.green, mean.green),
+            variance(squared_mean.blue, mean.blue),
+        )
+    }
+
+    /// Writes the buffers of the film, for a checkpoint
+    pub(crate) fn write_state(&self, writer: &mut impl Write) -> io::Result<()> {
+        write_u64(writer, self.width as u64)?;
+        write_u64(writer, self.height as u64)?;
+
+        write_u8(writer, self.aovs.len() as u8)?;
+        for aov in &self.aovs {
+            write_u8(
+                writer,
+                Aov::ALL.iter().position(|a| a == aov).unwrap() as u8,
+            )?;
+        }
+
+        for (&sum, &weight) in self.sums.iter().zip(&self.weights) {
+            write_color(writer, sum)?;
+            write_f64(writer, weight)?;
+        }
+
+        if self.aovs.is_empty() {
+            return Ok(());
+        }
+
+        for (index, hit) in self.hits.iter().enumerate() {
+            write_f64(writer, hit.weight)?;
+            write_color(writer, hit.albedo)?;
+            write_vect(writer, hit.normal)?;
+            write_f64(writer, hit.depth)?;
+            write_vect(writer, hit.position)?;
+
+            // The primitive index is stored as u64::MAX if there is none
+            let (best, primitive) = hit.primitive.map_or((0., u64::MAX), |(w, p)| (w, p as u64));
+            write_f64(writer, best)?;
+            write_u64(writer, primitive)?;
+
+            write_color(writer, self.squared_sums[index])?;
+            write_f64(writer, self.squared_weights[index])?;
+            write_u32(writer, self.sample_counts[index])?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads the buffers written by write_state into an empty film, which
+    /// must have the same size and AOVs
+    pub(crate) fn read_state(&mut self, reader: &mut impl Read) -> Result<(), CheckpointError> {
+        let width = read_u64(reader)? as usize;
+        let height = read_u64(reader)? as usize;
+        if (width, height) != (self.width, self.height) {
+            return Err(CheckpointError::SizeMismatch {
+                expected: (self.width, self.height),
+                found: (width, height),
+            });
+        }
+
+        let mut codes = vec![0; read_u8(reader)? as usize];
+        reader.read_exact(&mut codes)?;
+        let expected: Vec<u8> = self
+            .aovs
+            .iter()
+            .map(|aov| Aov::ALL.iter().position(|a| a == aov).unwrap() as u8)
+            .collect();
+        if codes != expected {
+            return Err(CheckpointError::AovMismatch);
+        }
+
+        for index in 0..width * height {
+            self.sums[index] = read_color(reader)?;
+            self.weights[index] = read_f64(reader)?;
+        }
+
+        if self.aovs.is_empty() {
+            return Ok(());
+        }
+
+        for index in 0..width * height {
+            let hit = &mut self.hits[index];
+            hit.weight = read_f64(reader)?;
+            hit.albedo = read_color(reader)?;
+            hit.normal = read_vect(reader)?;
+            hit.depth = read_f64(reader)?;
+            hit.position = read_vect(reader)?;
+
+            let best = read_f64(reader)?;
+            let primitive = read_u64(reader)?;
+            hit.primitive = (primitive != u64::MAX).then_some((best, primitive as usize));
+
+            self.squared_sums[index] = read_color(reader)?;
+            self.squared_weights[index] = read_f64(reader)?;
+            self.sample_counts[index] = read_u32(reader)?;
+        }
+
+        Ok(())
+    }
+
+    /// Exports the image to an OpenEXR file. If there are AOVs, they are
+    /// stored in other layers than the color, which is named "beauty".
+    pub fn export_exr(&self, filename: &str) {
+        if self.aovs.is_empty() {
+            self.image().export_exr(filename);
+            return;
+        }
+
+        let mut images = vec![("beauty", self.image())];
+        for &aov in &self.aovs {
+            images.push((aov.name(), self.aov_image(aov)));
+        }
+
+        let layers: Vec<(&str, &Image)> =
+            images.iter().map(|(name, image)| (*name, image)).collect();
+        export_exr_layers(&layers, filename);
+    }
+}
+
+fn write_vect(writer: &mut impl Write, v: Vect) -> io::Result<()> {
+    for value in [v.x, v.y, v.z] {
+        write_f64(writer, value)?;
+    }
+    Ok(())
+}
+
+fn read_vect(reader: &mut impl Read) -> io::Result<Vect> {
+    let x = read_f64(reader)?;
+    let y = read_f64(reader)?;
+    let z = read_f64(reader)?;
+    Ok(Vect::new(x, y, z))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filters::{BoxFilter, GaussianFilter, LanczosFilter, MitchellFilter, TentFilter};
+
+    #[test]
+    fn test_constant_image() {
+        let filters: Vec<Box<dyn Filter>> = vec![
+            BoxFilter::new(0.5),
+            TentFilter::new(1.5),
+            GaussianFilter::new(1.5, 0.5),
+            MitchellFilter::new(2., 1. / 3., 1. / 3.),
+            LanczosFilter::new(3.),
+        ];
+
+        // Whatever the filter, a constant signal gives a constant image
+        for filter in filters {
+            let mut film = Film::new(8, 6, filter);
+            let mut rng = fastrand::Rng::with_seed(3);
+
+            for y in 0..6 {
+                for x in 0..8 {
+                    for _ in 0..16 {
+                        let (sx, sy) = (x as f64 + rng.f64(), y as f64 + rng.f64());
+                        film.add_sample(sx, sy, Color::new(0.2, 0.5, 1.));
+                    }
+                }
+            }
+
+            let image = film.image();
+            for y in 0..6 {
+                for x in 0..8 {
+                    let color = image.pixel(x, y);
+                    assert!((color.red - 0.2).abs() <= 1e-9);
+                    assert!((color.green - 0.5).abs() <= 1e-9);
+                    assert!((color.blue - 1.).abs() <= 1e-9);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_box_filter() {
+        // A sample on the corner of a pixel only belongs to this pixel
+        let mut film = Film::new(2, 2, BoxFilter::new(0.5));
+        film.add_sample(1., 1., Color::white());
+
+        let image = film.image();
+        assert_eq!(image.pixel(1, 1).red, 1.);
+        assert_eq!(image.pixel(0, 0).red, 0.);
+        assert_eq!(image.pixel(0, 1).red, 0.);
+        assert_eq!(image.pixel(1, 0).red, 0.);
+    }
+
+    #[test]
+    fn test_aovs() {
+        let aovs = [Aov::Albedo, Aov::Depth, Aov::PrimitiveIndex, Aov::Variance];
+        let mut film = Film::new(2, 1, BoxFilter::new(0.5)).with_aovs(&aovs);
+
+        let hit = |depth, primitive| FirstHit {
+            albedo: Color::new(0.5, 0.5, 0.5),
+            normal: Vect::new(0., 0., 1.),
+            depth,
+            position: Vect::zero(),
+            primitive,
+        };
+
+        // Two samples hitting different primitives in the first pixel, none
+        // in the second one
+        film.add_sample_with_hit(0.5, 0.5, Color::new(1., 0., 0.), Some(hit(2., 3)));
+        film.add_sample_with_hit(0.2, 0.5, Color::new(3., 0., 0.), Some(hit(4., 7)));
+        film.add_sample(1.5, 0.5, Color::white());
+
+        let albedo = film.aov_image(Aov::Albedo);
+        assert_eq!(albedo.pixel(0, 0).green, 0.5);
+        assert_eq!(albedo.pixel(1, 0).green, 0.);
+
+        let depth = film.aov_image(Aov::Depth);
+        assert_eq!(depth.pixel(0, 0).red, 3.);
+        assert_eq!(depth.pixel(1, 0).red, f64::INFINITY);
+
+        let primitive = film.aov_image(Aov::PrimitiveIndex);
+        assert_eq!(primitive.pixel(0, 0).red, 3.);
+        assert_eq!(primitive.pixel(1, 0).red, -1.);
+
+        // Variance of the samples divided by their number
+        let variance = film.aov_image(Aov::Variance);
+        assert_eq!(variance.pixel(0, 0).red, 0.5);
+        assert_eq!(variance.pixel(0, 0).green, 0.);
+    }
+}
@@ -0,0 +1,30 @@
+use std::fmt::Debug;
+
+/// A reconstruction filter gives the weight of a sample in the value of the
+/// pixels around it.
+pub trait Filter: Send + Sync + Debug + FilterClone {
+    /// Returns the radius of the filter, in pixels. The weight of a sample
+    /// is 0 when it is further than the radius from the center of a pixel
+    /// along an axis.
+    fn radius(&self) -> f64;
+
+    /// Returns the weight of a sample at an offset (dx, dy) from the center
+    /// of a pixel. The weight can be negative.
+    fn evaluate(&self, dx: f64, dy: f64) -> f64;
+}
+
+pub trait FilterClone {
+    fn clone_box(&self) -> Box<dyn Filter>;
+}
+
+impl<T: Filter + Clone + 'static> FilterClone for T {
+    fn clone_box(&self) -> Box<dyn Filter> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn Filter> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
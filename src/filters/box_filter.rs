@@ -0,0 +1,33 @@
+use crate::Filter;
+
+/// Gives the same weight to all the samples in a square around the pixel.
+/// With a radius of 0.5, each sample only contributes to the pixel it falls
+/// in.
+#[derive(Clone, Debug)]
+pub struct BoxFilter {
+    radius: f64,
+}
+
+impl BoxFilter {
+    pub fn new(radius: f64) -> Box<Self> {
+        assert!(radius > 0.);
+        Box::new(Self { radius })
+    }
+}
+
+impl Filter for BoxFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, dx: f64, dy: f64) -> f64 {
+        // The square is half-open, so that a sample on the border between two
+        // pixels is only counted once
+        let range = -self.radius..self.radius;
+        if range.contains(&dx) && range.contains(&dy) {
+            1.
+        } else {
+            0.
+        }
+    }
+}
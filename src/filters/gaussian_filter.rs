@@ -0,0 +1,32 @@
+use crate::Filter;
+
+/// Gaussian of standard deviation sigma, truncated at the radius. The value at
+/// the radius is subtracted so that the filter goes smoothly to 0.
+#[derive(Clone, Debug)]
+pub struct GaussianFilter {
+    radius: f64,
+    sigma: f64,
+}
+
+impl GaussianFilter {
+    pub fn new(radius: f64, sigma: f64) -> Box<Self> {
+        assert!(radius > 0. && sigma > 0.);
+        Box::new(Self { radius, sigma })
+    }
+
+    fn gaussian(&self, x: f64) -> f64 {
+        (-x * x / (2. * self.sigma * self.sigma)).exp()
+    }
+}
+
+impl Filter for GaussianFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, dx: f64, dy: f64) -> f64 {
+        let edge = self.gaussian(self.radius);
+        let g = |x: f64| (self.gaussian(x) - edge).max(0.);
+        g(dx) * g(dy)
+    }
+}
@@ -0,0 +1,44 @@
+use std::f64::consts::PI;
+
+use crate::Filter;
+
+/// Sinc function windowed by a wider sinc, which keeps the sharpness of the
+/// ideal reconstruction filter with little ringing. The radius is the number
+/// of lobes of the window.
+#[derive(Clone, Debug)]
+pub struct LanczosFilter {
+    radius: f64,
+}
+
+impl LanczosFilter {
+    pub fn new(radius: f64) -> Box<Self> {
+        assert!(radius > 0.);
+        Box::new(Self { radius })
+    }
+
+    fn lanczos(&self, x: f64) -> f64 {
+        let sinc = |x: f64| {
+            if x.abs() < 1e-9 {
+                1.
+            } else {
+                (PI * x).sin() / (PI * x)
+            }
+        };
+
+        if x.abs() >= self.radius {
+            0.
+        } else {
+            sinc(x) * sinc(x / self.radius)
+        }
+    }
+}
+
+impl Filter for LanczosFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, dx: f64, dy: f64) -> f64 {
+        self.lanczos(dx) * self.lanczos(dy)
+    }
+}
@@ -0,0 +1,49 @@
+use crate::Filter;
+
+/// Cubic filter of Mitchell and Netravali. The parameters b and c control the
+/// tradeoff between blurring and ringing; b = c = 1/3 is the recommended
+/// value.
+#[derive(Clone, Debug)]
+pub struct MitchellFilter {
+    radius: f64,
+    b: f64,
+    c: f64,
+}
+
+impl MitchellFilter {
+    pub fn new(radius: f64, b: f64, c: f64) -> Box<Self> {
+        assert!(radius > 0.);
+        Box::new(Self { radius, b, c })
+    }
+
+    /// Evaluates the cubic, whose support is [-2; 2]
+    fn mitchell(&self, x: f64) -> f64 {
+        let (b, c) = (self.b, self.c);
+        let x = x.abs();
+
+        if x >= 2. {
+            0.
+        } else if x >= 1. {
+            ((-b - 6. * c) * x * x * x
+                + (6. * b + 30. * c) * x * x
+                + (-12. * b - 48. * c) * x
+                + (8. * b + 24. * c))
+                / 6.
+        } else {
+            ((12. - 9. * b - 6. * c) * x * x * x
+                + (-18. + 12. * b + 6. * c) * x * x
+                + (6. - 2. * b))
+                / 6.
+        }
+    }
+}
+
+impl Filter for MitchellFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, dx: f64, dy: f64) -> f64 {
+        self.mitchell(2. * dx / self.radius) * self.mitchell(2. * dy / self.radius)
+    }
+}
@@ -0,0 +1,11 @@
+pub mod box_filter;
+pub mod gaussian_filter;
+pub mod lanczos_filter;
+pub mod mitchell_filter;
+pub mod tent_filter;
+
+pub use box_filter::BoxFilter;
+pub use gaussian_filter::GaussianFilter;
+pub use lanczos_filter::LanczosFilter;
+pub use mitchell_filter::MitchellFilter;
+pub use tent_filter::TentFilter;
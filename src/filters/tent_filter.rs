@@ -0,0 +1,25 @@
+use crate::Filter;
+
+/// The weight decreases linearly from the center of the pixel, along each axis
+#[derive(Clone, Debug)]
+pub struct TentFilter {
+    radius: f64,
+}
+
+impl TentFilter {
+    pub fn new(radius: f64) -> Box<Self> {
+        assert!(radius > 0.);
+        Box::new(Self { radius })
+    }
+}
+
+impl Filter for TentFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, dx: f64, dy: f64) -> f64 {
+        let tent = |x: f64| (1. - x.abs() / self.radius).max(0.);
+        tent(dx) * tent(dy)
+    }
+}
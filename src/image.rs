@@ -1,5 +1,16 @@
-use crate::Color;
-use std::{fs::File, io::Write};
+use crate::{Color, DisplayPipeline};
+use exr::prelude::{
+    AnyChannel, AnyChannels, Encoding, FlatSamples, ImageAttributes, IntegerBounds, Layer,
+    LayerAttributes, ReadChannels, ReadLayers, SmallVec, WritableImage,
+};
+use image::{
+    codecs::hdr::{HdrDecoder, HdrEncoder},
+    Rgb,
+};
+use std::{
+    fs::{self, File},
+    io::{BufReader, BufWriter, Write},
+};
 
 pub struct Image {
     pixels: Vec<Vec<Color>>,
@@ -31,8 +42,15 @@ impl Image {
         self.pixels[x][y] = new_color;
     }
 
-    /// Exports the image to a given file
+    /// Exports the image to a given file, with 8 bits per component. The
+    /// values are clipped to [0; 1] and encoded in sRGB.
     pub fn export(&self, filename: &str) {
+        self.export_with(filename, &DisplayPipeline::default());
+    }
+
+    /// Exports the image to a given file, with 8 bits per component, after
+    /// converting the values with a display pipeline
+    pub fn export_with(&self, filename: &str, pipeline: &DisplayPipeline) {
         let mut output: image::RgbImage =
             image::ImageBuffer::new(self.width() as u32, self.height() as u32);
 
@@ -41,11 +59,7 @@ impl Image {
                 output.put_pixel(
                     x as u32,
                     (self.height() - y - 1) as u32,
-                    image::Rgb([
-                        (255. * self.pixels[x][y].red) as u8,
-                        (255. * self.pixels[x][y].green) as u8,
-                        (255. * self.pixels[x][y].blue) as u8,
-                    ]),
+                    image::Rgb(pipeline.to_srgb8(self.pixels[x][y], x, y)),
                 );
             }
         }
@@ -53,20 +67,285 @@ impl Image {
         output.save(filename).expect("Could not save the image");
     }
 
-    pub fn raw_export(&self, filename: &str) {
-        let mut file = File::create(filename).unwrap();
+    /// Exports the image to an OpenEXR file, without losing the values
+    /// outside of [0; 1]
+    pub fn export_exr(&self, filename: &str) {
+        export_exr_layers(&[("", self)], filename);
+    }
 
-        for x in 0..self.width() {
-            for y in 0..self.height() {
+    /// Exports the image to a Portable Float Map file
+    pub fn export_pfm(&self, filename: &str) {
+        let mut file = BufWriter::new(File::create(filename).unwrap());
+
+        // A negative scale means little-endian values. The lines are stored
+        // from the bottom to the top.
+        write!(file, "PF\n{} {}\n-1.0\n", self.width(), self.height()).unwrap();
+
+        for y in 0..self.height() {
+            for x in 0..self.width() {
                 let color = self.pixels[x][y];
+                for value in [color.red, color.green, color.blue] {
+                    file.write_all(&(value as f32).to_le_bytes()).unwrap();
+                }
+            }
+        }
+    }
+
+    /// Exports the image to a Radiance HDR file. The values are stored with 8
+    /// bits of mantissa and a shared exponent.
+    pub fn export_hdr(&self, filename: &str) {
+        let data: Vec<Rgb<f32>> = (0..self.height())
+            .rev()
+            .flat_map(|y| (0..self.width()).map(move |x| self.pixels[x][y].into()))
+            .collect();
+
+        HdrEncoder::new(BufWriter::new(File::create(filename).unwrap()))
+            .encode(&data, self.width(), self.height())
+            .expect("Could not save the image");
+    }
+
+    /// Loads the first layer of an OpenEXR file
+    pub fn load_exr(filename: &str) -> Self {
+        load_exr_layers(filename).swap_remove(0).1
+    }
+
+    /// Loads a Portable Float Map file, either in color or in grayscale
+    pub fn load_pfm(filename: &str) -> Self {
+        let bytes = fs::read(filename).unwrap();
+
+        // Header made of 4 tokens, followed by a single whitespace character
+        let mut position = 0;
+        let mut token = || {
+            while bytes[position].is_ascii_whitespace() {
+                position += 1;
+            }
+            let start = position;
+            while !bytes[position].is_ascii_whitespace() {
+                position += 1;
+            }
+            std::str::from_utf8(&bytes[start..position]).unwrap()
+        };
+
+        let channels = match token() {
+            "PF" => 3,
+            "Pf" => 1,
+            _ => panic!("Not a PFM file"),
+        };
+        let width: usize = token().parse().unwrap();
+        let height: usize = token().parse().unwrap();
+        let little_endian = token().parse::<f64>().unwrap() < 0.;
+
+        let values: Vec<f64> = bytes[position + 1..]
+            .chunks_exact(4)
+            .map(|chunk| {
+                let chunk = chunk.try_into().unwrap();
+                if little_endian {
+                    f32::from_le_bytes(chunk) as f64
+                } else {
+                    f32::from_be_bytes(chunk) as f64
+                }
+            })
+            .collect();
+        assert!(values.len() >= width * height * channels);
+
+        let mut image = Image::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let pixel = &values[(y * width + x) * channels..];
+                let color = if channels == 3 {
+                    Color::new(pixel[0], pixel[1], pixel[2])
+                } else {
+                    Color::new(pixel[0], pixel[0], pixel[0])
+                };
+                image.set_pixel(x, y, color);
+            }
+        }
+
+        image
+    }
+
+    /// Loads a Radiance HDR file
+    pub fn load_hdr(filename: &str) -> Self {
+        let decoder = HdrDecoder::new(BufReader::new(File::open(filename).unwrap()))
+            .expect("Could not read the image");
+        let (width, height) = (
+            decoder.metadata().width as usize,
+            decoder.metadata().height as usize,
+        );
+        let data = decoder.read_image_hdr().expect("Could not read the image");
+
+        let mut image = Image::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let Rgb([red, green, blue]) = data[(height - y - 1) * width + x];
+                image.set_pixel(x, y, Color::new(red as f64, green as f64, blue as f64));
+            }
+        }
 
-                file.write_all(color.red.to_string().as_bytes()).unwrap();
-                file.write_all(b" ").unwrap();
-                file.write_all(color.green.to_string().as_bytes()).unwrap();
-                file.write_all(b" ").unwrap();
-                file.write_all(color.blue.to_string().as_bytes()).unwrap();
-                file.write_all(b"\n").unwrap();
+        image
+    }
+}
+
+impl From<Color> for Rgb<f32> {
+    fn from(color: Color) -> Self {
+        Rgb([color.red as f32, color.green as f32, color.blue as f32])
+    }
+}
+
+/// Exports several images of the same size to the layers of an OpenEXR file.
+/// The layers are named, except if there is a single layer with an empty name.
+pub fn export_exr_layers(layers: &[(&str, &Image)], filename: &str) {
+    let (width, height) = (layers[0].1.width(), layers[0].1.height());
+
+    let layers: Vec<Layer<AnyChannels<FlatSamples>>> = layers
+        .iter()
+        .map(|&(name, image)| {
+            assert!(image.width() == width && image.height() == height);
+
+            // The lines are stored from the top to the bottom
+            let channel = |name: &str, value: fn(Color) -> f64| {
+                let samples = (0..height)
+                    .rev()
+                    .flat_map(|y| (0..width).map(move |x| value(image.pixel(x, y)) as f32))
+                    .collect();
+                AnyChannel::new(name, FlatSamples::F32(samples))
+            };
+            let channels = SmallVec::from_vec(vec![
+                channel("R", |color| color.red),
+                channel("G", |color| color.green),
+                channel("B", |color| color.blue),
+            ]);
+
+            let attributes = if name.is_empty() {
+                LayerAttributes::default()
+            } else {
+                LayerAttributes::named(name)
+            };
+
+            Layer::new(
+                (width, height),
+                attributes,
+                Encoding::FAST_LOSSLESS,
+                AnyChannels::sort(channels),
+            )
+        })
+        .collect();
+
+    exr::prelude::Image::from_layers(
+        ImageAttributes::new(IntegerBounds::from_dimensions((width, height))),
+        layers,
+    )
+    .write()
+    .to_file(filename)
+    .expect("Could not save the image");
+}
+
+/// Loads all the layers of an OpenEXR file, with their names
+pub fn load_exr_layers(filename: &str) -> Vec<(String, Image)> {
+    let file = exr::prelude::read()
+        .no_deep_data()
+        .largest_resolution_level()
+        .all_channels()
+        .all_layers()
+        .all_attributes()
+        .from_file(filename)
+        .expect("Could not read the image");
+
+    file.layer_data
+        .iter()
+        .map(|layer| {
+            let (width, height) = (layer.size.width(), layer.size.height());
+
+            let channel = |name: &str| -> Vec<f32> {
+                layer
+                    .channel_data
+                    .list
+                    .iter()
+                    .find(|channel| channel.name.eq(name))
+                    .expect("Missing channel")
+                    .sample_data
+                    .values_as_f32()
+                    .collect()
+            };
+            let (red, green, blue) = (channel("R"), channel("G"), channel("B"));
+
+            let mut image = Image::new(width, height);
+            for y in 0..height {
+                for x in 0..width {
+                    let index = (height - y - 1) * width + x;
+                    image.set_pixel(
+                        x,
+                        y,
+                        Color::new(red[index] as f64, green[index] as f64, blue[index] as f64),
+                    );
+                }
+            }
+
+            let name = layer
+                .attributes
+                .layer_name
+                .as_ref()
+                .map_or(String::new(), |name| name.to_string());
+
+            (name, image)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hdr_formats() {
+        let mut image = Image::new(5, 3);
+        for x in 0..5 {
+            for y in 0..3 {
+                image.set_pixel(x, y, Color::new(x as f64 * 10., y as f64 * 0.1, 0.5));
             }
         }
+        let mut other = Image::new(5, 3);
+        other.set_pixel(1, 2, Color::new(-1., 2., 1e6));
+
+        let folder = std::env::temp_dir();
+        let path = |extension: &str| {
+            let name = format!("renderer-test-{}.{extension}", std::process::id());
+            folder.join(name).to_str().unwrap().to_string()
+        };
+
+        let check = |loaded: &Image, expected: &Image, tolerance: f64| {
+            assert_eq!(loaded.width(), expected.width());
+            assert_eq!(loaded.height(), expected.height());
+            for x in 0..expected.width() {
+                for y in 0..expected.height() {
+                    let (a, b) = (loaded.pixel(x, y), expected.pixel(x, y));
+                    let scale = b.red.abs().max(b.green.abs()).max(b.blue.abs()).max(1e-3);
+                    for (u, v) in [(a.red, b.red), (a.green, b.green), (a.blue, b.blue)] {
+                        assert!((u - v).abs() <= tolerance * scale);
+                    }
+                }
+            }
+        };
+
+        image.export_pfm(&path("pfm"));
+        check(&Image::load_pfm(&path("pfm")), &image, 1e-6);
+
+        // 8 bits of mantissa, shared by the components
+        image.export_hdr(&path("hdr"));
+        check(&Image::load_hdr(&path("hdr")), &image, 1e-2);
+
+        export_exr_layers(&[("beauty", &image), ("other", &other)], &path("exr"));
+        let layers = load_exr_layers(&path("exr"));
+        assert_eq!(layers.len(), 2);
+        assert_eq!(
+            (layers[0].0.as_str(), layers[1].0.as_str()),
+            ("beauty", "other")
+        );
+        check(&layers[0].1, &image, 1e-6);
+        check(&layers[1].1, &other, 1e-6);
+
+        for extension in ["pfm", "hdr", "exr"] {
+            fs::remove_file(path(extension)).unwrap();
+        }
     }
 }
@@ -1,7 +1,17 @@
+pub mod animated_transform;
+pub mod blue_noise;
 pub mod bounding_box;
 pub mod bsdf;
+pub mod bvh;
 pub mod camera;
+pub mod cameras;
+pub mod checkpoint;
 pub mod color;
+pub mod denoiser;
+pub mod display;
+pub mod film;
+pub mod filter;
+pub mod filters;
 pub mod image;
 pub mod light;
 pub mod load_obj;
@@ -9,14 +19,18 @@ pub mod material;
 pub mod matrix;
 pub mod primitive;
 pub mod primitives;
+pub mod progress;
 pub mod ray;
 pub mod renderer;
 pub mod renderers;
 pub mod sampler;
+pub mod sampler_quality;
 pub mod samplers;
 pub mod scene;
+pub mod scheduler;
 pub mod shape;
 pub mod shapes;
+pub mod stats;
 pub mod texture;
 pub mod textures;
 pub mod transform;
@@ -25,18 +39,28 @@ pub mod warping;
 
 pub use crate::image::Image;
 pub use crate::renderer::Renderer;
+pub use animated_transform::AnimatedTransform;
 pub use bounding_box::BoundingBox;
 pub use bsdf::BSDF;
+pub use bvh::Bvh;
 pub use camera::Camera;
+pub use checkpoint::CheckpointError;
 pub use color::Color;
+pub use denoiser::Denoiser;
+pub use display::DisplayPipeline;
+pub use film::{Aov, Film};
+pub use filter::Filter;
 pub use light::Light;
 pub use load_obj::load_obj;
 pub use material::Material;
 pub use matrix::Matrix4x4;
 pub use primitive::Primitive;
+pub use progress::{CancellationToken, Progress};
 pub use ray::Ray;
 pub use scene::Scene;
+pub use scheduler::Scheduler;
 pub use shape::{Collision, Shape};
+pub use stats::RenderStats;
 pub use texture::Texture;
 pub use transform::Transform;
 pub use vect::Vect;
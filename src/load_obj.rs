@@ -40,21 +40,34 @@ pub fn load_obj(filename: &str) -> Vec<(String, Box<dyn Shape>)> {
             );
             vertices.push(v);
         } else if tokens[0] == "f" {
-            fn get_index(face: &str) -> usize {
-                face.split('/').next().unwrap().parse::<usize>().unwrap() - 1
-            }
+            // Indices start at 1, and negative indices are relative to the
+            // last vertex
+            let get_index = |face: &str| {
+                let index = face.split('/').next().unwrap().parse::<isize>().unwrap();
+                if index < 0 {
+                    (vertices.len() as isize + index) as usize
+                } else {
+                    index as usize - 1
+                }
+            };
 
-            let triangle = Triangle::new(
-                vertices[get_index(tokens[1])],
-                vertices[get_index(tokens[2])],
-                vertices[get_index(tokens[3])],
-            );
+            let shape = shape.get_or_insert_with(CompoundShape::new);
 
-            shape.as_mut().unwrap().add(triangle);
+            // Polygons are split in a fan of triangles sharing the first vertex
+            let first = vertices[get_index(tokens[1])];
+            for i in 2..tokens.len() - 1 {
+                shape.add(Triangle::new(
+                    first,
+                    vertices[get_index(tokens[i])],
+                    vertices[get_index(tokens[i + 1])],
+                ));
+            }
         }
     }
 
-    objects.push((name, shape.unwrap()));
+    if let Some(shape) = shape {
+        objects.push((name, shape));
+    }
 
     objects
 }
@@ -34,6 +34,38 @@ impl Matrix4x4 {
             z: m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z + m[2][3] * cst,
         }
     }
+
+    /// Returns the inverse of the matrix. The matrix must be invertible.
+    pub fn inverse(&self) -> Self {
+        let m = &self.m;
+
+        // Cofactors of the upper-left 3x3 block
+        let c00 = m[1][1] * m[2][2] - m[1][2] * m[2][1];
+        let c01 = m[1][2] * m[2][0] - m[1][0] * m[2][2];
+        let c02 = m[1][0] * m[2][1] - m[1][1] * m[2][0];
+
+        let det = m[0][0] * c00 + m[0][1] * c01 + m[0][2] * c02;
+        assert!(det != 0.);
+        let inv_det = 1. / det;
+
+        let mut inv = [[0.; 4]; 3];
+        inv[0][0] = c00 * inv_det;
+        inv[1][0] = c01 * inv_det;
+        inv[2][0] = c02 * inv_det;
+        inv[0][1] = (m[0][2] * m[2][1] - m[0][1] * m[2][2]) * inv_det;
+        inv[1][1] = (m[0][0] * m[2][2] - m[0][2] * m[2][0]) * inv_det;
+        inv[2][1] = (m[0][1] * m[2][0] - m[0][0] * m[2][1]) * inv_det;
+        inv[0][2] = (m[0][1] * m[1][2] - m[0][2] * m[1][1]) * inv_det;
+        inv[1][2] = (m[0][2] * m[1][0] - m[0][0] * m[1][2]) * inv_det;
+        inv[2][2] = (m[0][0] * m[1][1] - m[0][1] * m[1][0]) * inv_det;
+
+        // The translation is inverted after the linear part
+        for line in inv.iter_mut() {
+            line[3] = -(line[0] * m[0][3] + line[1] * m[1][3] + line[2] * m[2][3]);
+        }
+
+        Self { m: inv }
+    }
 }
 
 impl Mul for &Matrix4x4 {
@@ -42,11 +74,11 @@ impl Mul for &Matrix4x4 {
     fn mul(self, rhs: Self) -> Matrix4x4 {
         let mut m = [[0.; 4]; 3];
 
-        for i in 0..3 {
-            m[i][3] = self.m[i][3];
-            for j in 0..4 {
+        for (i, line) in m.iter_mut().enumerate() {
+            line[3] = self.m[i][3];
+            for (j, entry) in line.iter_mut().enumerate() {
                 for k in 0..3 {
-                    m[i][j] += self.m[i][k] * rhs.m[k][j];
+                    *entry += self.m[i][k] * rhs.m[k][j];
                 }
             }
         }
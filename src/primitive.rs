@@ -1,15 +1,26 @@
-use std::fmt::Debug;
+use std::{collections::HashSet, fmt::Debug};
 
 use crate::material::Material;
 use crate::ray::Ray;
 use crate::shape::Collision;
+use crate::BoundingBox;
 
 /// A primitive is something that can be rendered.
 pub trait Primitive: Send + Sync + PrimitiveClone + Debug {
+    /// Returns a bounding box (idealy the smallest) containing the primitive
+    fn bounding_box(&self) -> BoundingBox;
+
     fn collision_date(&self, ray: Ray) -> Option<f64>;
     fn collision(&self, ray: Ray) -> Option<Collision>;
 
     fn material_at_collision(&self, collision: Collision) -> Material;
+
+    /// Returns the memory used by the acceleration structures of the primitive, in
+    /// bytes. The shared objects whose address is in counted are not counted
+    /// again.
+    fn memory_size(&self, _counted: &mut HashSet<*const ()>) -> usize {
+        0
+    }
 }
 
 pub trait PrimitiveClone {
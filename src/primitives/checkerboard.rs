@@ -4,6 +4,7 @@ use crate::primitive::Primitive;
 use crate::ray::Ray;
 use crate::shape::Collision;
 use crate::vect::Vect;
+use crate::BoundingBox;
 
 /// The checkboard is necessary horizontal
 /// The lines are along the x axis, and the columns along the y axis
@@ -39,11 +40,15 @@ impl Checkerboard {
 }
 
 impl Primitive for Checkerboard {
+    fn bounding_box(&self) -> BoundingBox {
+        BoundingBox::new_from_extremities(
+            self.origin,
+            self.origin + Vect::new(self.width, self.height, 0.),
+        )
+    }
+
     fn collision_date(&self, ray: Ray) -> Option<f64> {
-        match self.collision(ray) {
-            None => None,
-            Some(collision) => Some(collision.date),
-        }
+        self.collision(ray).map(|collision| collision.date)
     }
 
     fn collision(&self, ray: Ray) -> Option<Collision> {
@@ -67,6 +72,10 @@ impl Primitive for Checkerboard {
                 date: collision_date,
                 pos,
                 normal: Vect::new(0., 0., 1.),
+                uv: (
+                    (pos.x - self.origin.x) / self.width,
+                    (pos.y - self.origin.y) / self.height,
+                ),
             })
         } else {
             None
@@ -80,7 +89,7 @@ impl Primitive for Checkerboard {
             ((collision.pos.y - self.origin.y) / self.height * self.lines_count as f64) as usize;
 
         Material {
-            color: self.colors[((index_x + index_y) % 2) as usize],
+            color: self.colors[(index_x + index_y) % 2],
         }
     }
 }
@@ -0,0 +1,46 @@
+use crate::{
+    shapes::implicit_shapes::Fractal, BoundingBox, Collision, Color, Material, Primitive, Ray,
+    Shape,
+};
+
+/// A fractal whose color depends on the orbit trap at the point of collision.
+/// The color is interpolated linearly between two colors.
+#[derive(Clone, Debug)]
+pub struct FractalPrimitive<F: Fractal + Clone + 'static> {
+    fractal: Box<F>,
+    colors: [Color; 2],
+}
+
+impl<F: Fractal + Clone + 'static> FractalPrimitive<F> {
+    pub fn new(fractal: Box<F>, color1: Color, color2: Color) -> Box<Self> {
+        Box::new(Self {
+            fractal,
+            colors: [color1, color2],
+        })
+    }
+}
+
+impl<F: Fractal + Clone + 'static> Primitive for FractalPrimitive<F> {
+    fn bounding_box(&self) -> BoundingBox {
+        self.fractal.bounding_box()
+    }
+
+    fn collision_date(&self, ray: Ray) -> Option<f64> {
+        self.fractal.collision_date(ray)
+    }
+
+    fn collision(&self, ray: Ray) -> Option<Collision> {
+        self.fractal.collision(ray)
+    }
+
+    fn material_at_collision(&self, collision: Collision) -> Material {
+        let t = self.fractal.orbit_trap(collision.pos);
+        let [c1, c2] = self.colors;
+
+        Material::new(Color::new(
+            (1. - t) * c1.red + t * c2.red,
+            (1. - t) * c1.green + t * c2.green,
+            (1. - t) * c1.blue + t * c2.blue,
+        ))
+    }
+}
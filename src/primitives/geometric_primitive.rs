@@ -1,4 +1,6 @@
-use crate::{Collision, Material, Primitive, Ray, Shape};
+use std::collections::HashSet;
+
+use crate::{BoundingBox, Collision, Material, Primitive, Ray, Shape};
 
 #[derive(Clone, Debug)]
 pub struct GeometricPrimitive {
@@ -13,6 +15,10 @@ impl GeometricPrimitive {
 }
 
 impl Primitive for GeometricPrimitive {
+    fn bounding_box(&self) -> BoundingBox {
+        self.shape.bounding_box()
+    }
+
     fn collision_date(&self, ray: Ray) -> Option<f64> {
         self.shape.collision_date(ray)
     }
@@ -24,4 +30,8 @@ impl Primitive for GeometricPrimitive {
     fn material_at_collision(&self, _collision: Collision) -> Material {
         self.material
     }
+
+    fn memory_size(&self, counted: &mut HashSet<*const ()>) -> usize {
+        self.shape.memory_size(counted)
+    }
 }
@@ -1,7 +1,9 @@
 pub mod checkerboard;
+pub mod fractal_primitive;
 pub mod geometric_primitive;
 pub mod transformed_primitive;
 
 pub use checkerboard::Checkerboard;
+pub use fractal_primitive::FractalPrimitive;
 pub use geometric_primitive::GeometricPrimitive;
 pub use transformed_primitive::TransformedPrimitive;
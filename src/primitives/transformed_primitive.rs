@@ -1,44 +1,125 @@
+use std::{collections::HashSet, sync::Arc};
+
 use crate::{
-    material::Material, primitive::Primitive, ray::Ray, shape::Collision, transform::Transform,
+    animated_transform::AnimatedTransform, material::Material, primitive::Primitive, ray::Ray,
+    shape::Collision, transform::Transform, BoundingBox,
 };
 
-/// Applies an affine transformation on a primitive
+/// Applies an affine transformation on a primitive. The primitive can be
+/// shared between several transformed primitives, so that a mesh used many
+/// times in a scene (and its acceleration structure) is only stored once.
+/// The transformation may change over time, in which case the primitive is
+/// seen at a different place by rays at different times.
 #[derive(Clone, Debug)]
 pub struct TransformedPrimitive {
-    primitive: Box<dyn Primitive>,
-    object_to_world: Transform, // Object to world
+    primitive: Arc<dyn Primitive>,
+    object_to_world: AnimatedTransform, // Object to world
+    bounding_box: BoundingBox,          // Covers the whole motion
 }
 
 impl TransformedPrimitive {
     pub fn new(primitive: Box<dyn Primitive>, object_to_world: Transform) -> Box<Self> {
+        Self::new_instance(Arc::from(primitive), object_to_world)
+    }
+
+    /// Returns a new instance of a shared primitive. Cloning the instance
+    /// does not copy the primitive.
+    pub fn new_instance(primitive: Arc<dyn Primitive>, object_to_world: Transform) -> Box<Self> {
+        Self::new_animated_instance(primitive, AnimatedTransform::from(object_to_world))
+    }
+
+    /// Returns a moving primitive
+    pub fn new_animated(
+        primitive: Box<dyn Primitive>,
+        object_to_world: AnimatedTransform,
+    ) -> Box<Self> {
+        Self::new_animated_instance(Arc::from(primitive), object_to_world)
+    }
+
+    /// Returns a moving instance of a shared primitive
+    pub fn new_animated_instance(
+        primitive: Arc<dyn Primitive>,
+        object_to_world: AnimatedTransform,
+    ) -> Box<Self> {
+        let bounding_box = object_to_world.motion_bounding_box(&primitive.bounding_box());
+
         Box::new(TransformedPrimitive {
             primitive,
             object_to_world,
+            bounding_box,
         })
     }
 }
 
 impl Primitive for TransformedPrimitive {
+    fn bounding_box(&self) -> BoundingBox {
+        self.bounding_box.clone()
+    }
+
     fn collision_date(&self, ray: Ray) -> Option<f64> {
         self.primitive
-            .collision_date(self.object_to_world.apply_inv_ray(ray))
+            .collision_date(self.object_to_world.at(ray.time).apply_inv_ray(ray))
     }
 
     fn collision(&self, ray: Ray) -> Option<Collision> {
-        match self
-            .primitive
-            .collision(self.object_to_world.apply_inv_ray(ray))
-        {
-            None => None,
-            Some(col) => Some(Collision {
+        let object_to_world = self.object_to_world.at(ray.time);
+
+        self.primitive
+            .collision(object_to_world.apply_inv_ray(ray))
+            .map(|col| Collision {
                 date: col.date,
-                pos: self.object_to_world.apply_point(col.pos),
-                normal: self.object_to_world.apply_normal(col.normal).normalized(),
-            }),
-        }
+                pos: object_to_world.apply_point(col.pos),
+                normal: object_to_world.apply_normal(col.normal).normalized(),
+                uv: col.uv,
+            })
     }
 
     fn material_at_collision(&self, collision: Collision) -> Material {
         self.primitive.material_at_collision(collision)
     }
+
+    /// Counts the shared primitive only for its first instance
+    fn memory_size(&self, counted: &mut HashSet<*const ()>) -> usize {
+        if counted.insert(Arc::as_ptr(&self.primitive).cast()) {
+            self.primitive.memory_size(counted)
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{primitives::GeometricPrimitive, shapes::Sphere, Color, Vect};
+
+    #[test]
+    fn test_animated_primitive() {
+        // A sphere moving from the origin at time 0 to (0, 5, 0) at time 1
+        let primitive = TransformedPrimitive::new_animated(
+            GeometricPrimitive::new(Sphere::new(Vect::zero(), 1.), Material::new(Color::white())),
+            AnimatedTransform::new(
+                0.,
+                Transform::new_identity(),
+                1.,
+                Transform::new_translation(Vect::new(0., 5., 0.)),
+            ),
+        );
+
+        let ray = |time| Ray {
+            time,
+            ..Ray::new(Vect::new(-5., 0., 0.), Vect::new(1., 0., 0.))
+        };
+        assert_eq!(primitive.collision_date(ray(0.)), Some(4.));
+        assert_eq!(primitive.collision_date(ray(1.)), None);
+
+        // Halfway, the sphere is at (0, 2.5, 0)
+        let ray = Ray {
+            time: 0.5,
+            ..Ray::new(Vect::new(-5., 2.5, 0.), Vect::new(1., 0., 0.))
+        };
+        let collision = primitive.collision(ray).unwrap();
+        assert!((collision.pos - Vect::new(-1., 2.5, 0.)).norm() < 1e-9);
+        assert!((collision.normal - Vect::new(-1., 0., 0.)).norm() < 1e-9);
+    }
 }
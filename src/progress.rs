@@ -0,0 +1,107 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// State of a render, given to the progress callbacks
+#[derive(Clone, Copy, Debug)]
+pub struct Progress {
+    /// Part of the work done, in [0; 1]
+    pub fraction: f64,
+
+    pub elapsed: Duration,
+
+    /// Estimated remaining time, unknown until some work is done
+    pub eta: Option<Duration>,
+
+    pub rays_per_second: f64,
+}
+
+/// Function called by the threads of a render each time some work is done
+pub type ProgressCallback = Arc<dyn Fn(&Progress) + Send + Sync>;
+
+/// Stops a render from another thread. The render returns as soon as possible,
+/// with what has been computed so far.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Counts the work done by the threads of a render, in units chosen by the
+/// renderer (pixels, samples...), and reports it to a callback
+pub struct ProgressTracker {
+    callback: Option<ProgressCallback>,
+    start: Instant,
+    total: u64,
+    done: AtomicU64,
+    rays: AtomicU64,
+
+    // If set, the fraction done is at least the fraction of the budget spent
+    time_budget: Option<Duration>,
+}
+
+impl ProgressTracker {
+    pub fn new(callback: Option<ProgressCallback>, total: u64) -> Self {
+        Self {
+            callback,
+            start: Instant::now(),
+            total,
+            done: AtomicU64::new(0),
+            rays: AtomicU64::new(0),
+            time_budget: None,
+        }
+    }
+
+    pub fn with_time_budget(mut self, time_budget: Option<Duration>) -> Self {
+        self.time_budget = time_budget;
+        self
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Adds some work done and the number of rays traced for it, and calls
+    /// the callback
+    pub fn advance(&self, work: u64, rays: u64) {
+        let done = self.done.fetch_add(work, Ordering::Relaxed) + work;
+        let rays = self.rays.fetch_add(rays, Ordering::Relaxed) + rays;
+
+        if let Some(callback) = &self.callback {
+            callback(&self.progress(done, rays));
+        }
+    }
+
+    fn progress(&self, done: u64, rays: u64) -> Progress {
+        let elapsed = self.elapsed();
+
+        let mut fraction = (done as f64 / self.total.max(1) as f64).min(1.);
+        if let Some(budget) = self.time_budget {
+            fraction = fraction.max((elapsed.as_secs_f64() / budget.as_secs_f64()).min(1.));
+        }
+
+        Progress {
+            fraction,
+            elapsed,
+            eta: (fraction > 0.).then(|| elapsed.mul_f64((1. - fraction) / fraction)),
+            rays_per_second: rays as f64 / elapsed.as_secs_f64().max(1e-9),
+        }
+    }
+}
@@ -4,12 +4,20 @@ use crate::vect::Vect;
 pub struct Ray {
     pub pos: Vect,
     pub dir: Vect, // Not necessarily normalized
+    pub time: f64,
 }
 
 impl Ray {
     pub fn new(pos: Vect, mut dir: Vect) -> Self {
         dir.normalize();
-        Ray { pos, dir }
+        Ray { pos, dir, time: 0. }
+    }
+
+    /// Returns the same ray, at a given time. Rays at different times may
+    /// see moving objects at different places.
+    pub fn with_time(mut self, time: f64) -> Self {
+        self.time = time;
+        self
     }
 
     pub fn pos(&self) -> Vect {
@@ -1,6 +1,36 @@
-use crate::Scene;
+use crate::{Image, RenderStats, Scene, Scheduler};
+use std::time::Instant;
 
 pub trait Renderer {
-    /// Renders the scene
-    fn render(&self, scene: Scene);
+    /// Renders the scene and returns the image, which can then be saved
+    /// with Image::export
+    fn render(&self, scene: Scene) -> Image;
+
+    /// Returns the scheduler running the threads of the renders, if any. The
+    /// statistics of the renders are collected from its threads, so without
+    /// one render_with_stats only measures the time of the render.
+    fn scheduler(&self) -> Option<&Scheduler> {
+        None
+    }
+
+    /// Renders the scene and returns the image with the statistics of the
+    /// render, which can be printed as a table
+    fn render_with_stats(&self, scene: Scene) -> (Image, RenderStats) {
+        let acceleration_memory = scene.acceleration_memory();
+
+        let (image, mut stats) = match self.scheduler() {
+            Some(scheduler) => scheduler.collect_stats(|| self.render(scene)),
+            None => {
+                let start = Instant::now();
+                let image = self.render(scene);
+                let stats = RenderStats {
+                    elapsed: start.elapsed(),
+                    ..RenderStats::default()
+                };
+                (image, stats)
+            }
+        };
+        stats.acceleration_memory = acceleration_memory;
+        (image, stats)
+    }
 }
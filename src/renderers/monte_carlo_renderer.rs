@@ -1,11 +1,20 @@
-use crate::sampler::Sampler;
+use crate::checkpoint::{Checkpoint, CheckpointError};
+use crate::film::FirstHit;
+use crate::filters::BoxFilter;
+use crate::sampler::{mix_seed, Sampler};
+use crate::scheduler::{Scheduler, Tile};
+use crate::stats::{self, RayKind};
 use crate::warping::to_cosine_directed_hemisphere;
-use crate::{Color, Image, Ray, Renderer, Scene};
+use crate::{
+    Aov, Collision, Color, Denoiser, Film, Filter, Image, Primitive, Ray, Renderer, Scene,
+};
 use derive_builder::Builder;
 use std::{
+    any::type_name,
     f64::consts::PI,
-    sync::{mpsc, Arc},
-    thread,
+    ops::Range,
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
 #[derive(Builder)]
@@ -13,254 +22,675 @@ use std::{
 pub struct MonteCarloRenderer<S: Sampler> {
     steps_count: u32,
     iterations_per_step_count: u32,
-    output_folder: String,
+
+    // If set, called with the number of steps done and the film after each
+    // step, for instance to save the intermediate images
+    #[builder(default, setter(custom))]
+    on_step: Option<StepCallback>,
 
     #[builder(default = "Color::black()")]
     ambient_occlusion: Color,
 
-    // Given a unique ID representing the thread, returns a new sampler
-    sampler_factory: fn(usize) -> S,
+    // Reconstruction filter used to compute the pixels from the samples. By
+    // default, a sample only contributes to the pixel it falls in.
+    #[builder(default = "BoxFilter::new(0.5)")]
+    filter: Box<dyn Filter>,
+
+    // Cloned by each thread. The samples only depend on the seed of the
+    // sampler, on the pixel and on the index of the sample, so the image does
+    // not depend on the thread computing each pixel.
+    sampler: S,
+
+    // Splits the image into tiles rendered by several threads. By default,
+    // there is one thread per core.
+    #[builder(default)]
+    scheduler: Scheduler,
+
+    // Auxiliary buffers recorded by the film returned by render_film
+    #[builder(default)]
+    aovs: Vec<Aov>,
+
+    // If set, the image returned by render is denoised. The AOVs needed by
+    // the denoiser are then recorded by the film.
+    #[builder(default, setter(strip_option))]
+    denoiser: Option<Denoiser>,
+
+    // Adaptive sampling: if set, a pixel having at least min_samples_per_pixel
+    // samples gets no more samples once the standard error of its luminance,
+    // relative to its mean, is below this threshold. The pixels get at most
+    // steps_count * iterations_per_step_count samples.
+    #[builder(default, setter(strip_option))]
+    error_threshold: Option<f64>,
+
+    #[builder(default)]
+    min_samples_per_pixel: u32,
+
+    // Progressive rendering: if set, no tile is started once the render has
+    // lasted this long, so the render stops at most a tile after the budget.
+    // The pixels of the tiles rendered in the last step have more samples
+    // than the others. With error_threshold, the render stops when either the
+    // budget or the noise level is reached, and steps_count is only an upper
+    // bound.
+    #[builder(default, setter(strip_option))]
+    time_budget: Option<Duration>,
+
+    // If set, the state of the render is saved to this file after each step,
+    // so that the render can be resumed with resume_film
+    #[builder(default, setter(into, strip_option))]
+    checkpoint: Option<String>,
 }
 
-fn one_color(ray: Ray, scene: &Scene, sample: [f64; 2], ambient_occlusion: Color) -> Color {
-    if let Some((primitive, collision)) = scene.collision(ray) {
-        let material = primitive.material_at_collision(collision);
+/// Function called by a render after each step
+pub type StepCallback = Arc<dyn Fn(usize, &Film) + Send + Sync>;
 
-        let color = primitive.material_at_collision(collision).color;
+impl<S: Sampler> MonteCarloRendererBuilder<S> {
+    /// Calls a function with the number of steps done and the film after each
+    /// step of the render
+    pub fn on_step(mut self, callback: impl Fn(usize, &Film) + Send + Sync + 'static) -> Self {
+        self.on_step = Some(Some(Arc::new(callback)));
+        self
+    }
+}
 
-        // We hit the light
-        if color.red == 1. && color.blue == 1. && color.green == 0. {
-            return Color::white();
+/// Returns the color of a camera ray, and the first surface it hits with the
+/// index of its primitive in the scene
+fn one_color(
+    ray: Ray,
+    scene: &Scene,
+    sample: [f64; 2],
+    ambient_occlusion: Color,
+) -> (Color, Option<(usize, &dyn Primitive, Collision)>) {
+    stats::count_ray(RayKind::Camera);
+    let hit = scene.indexed_collision(ray);
+
+    let color = match hit {
+        Some((_, primitive, collision)) => {
+            surface_color(ray, scene, primitive, collision, sample, ambient_occlusion)
         }
+        None => {
+            stats::count_path(1);
+            Color::black()
+        }
+    };
+
+    (color, hit)
+}
+
+/// Returns the light coming from a surface hit by a ray
+fn surface_color(
+    ray: Ray,
+    scene: &Scene,
+    primitive: &dyn Primitive,
+    collision: Collision,
+    sample: [f64; 2],
+    ambient_occlusion: Color,
+) -> Color {
+    let material = primitive.material_at_collision(collision);
 
-        // Compute the intensity
-        /*let mut intensity = 0.;
+    let color = primitive.material_at_collision(collision).color;
 
-        for light in scene.lights.iter() {
-            let to_light = light.pos - collision.pos;
+    // We hit the light
+    if color.red == 1. && color.blue == 1. && color.green == 0. {
+        stats::count_path(1);
+        return Color::white();
+    }
 
-            // Check if an ogject hides the light
-            let mut ray_to_light = Ray::new(collision.pos, to_light);
-            ray_to_light.move_by(1e-3); // TODO make this value depend on the scene
+    // Compute the intensity
+    /*let mut intensity = 0.;
 
-            if scene.collision_date(ray_to_light) >= to_light.norm() {
-                let intensity_light = light.intensity * (ray_to_light.dir() * collision.normal);
+    for light in scene.lights.iter() {
+        let to_light = light.pos - collision.pos;
 
-                if intensity_light > 0. {
-                    intensity += intensity_light;
-                }
+        // Check if an ogject hides the light
+        let mut ray_to_light = Ray::new(collision.pos, to_light);
+        ray_to_light.move_by(1e-3); // TODO make this value depend on the scene
+
+        if scene.collision_date(ray_to_light) >= to_light.norm() {
+            let intensity_light = light.intensity * (ray_to_light.dir() * collision.normal);
+
+            if intensity_light > 0. {
+                intensity += intensity_light;
             }
         }
+    }
 
-        Color {
-            red: intensity * material.color.red,
-            green: intensity * material.color.green,
-            blue: intensity * material.color.blue,
-        }*/
-
-        // Importance sampling
-        let next_dir = to_cosine_directed_hemisphere(collision.normal, sample);
-        let mut next_ray = Ray {
-            pos: collision.pos,
-            dir: next_dir,
-        };
-        next_ray.move_by(1e-3);
-
-        if let Some((next_primitive, next_col)) = scene.collision(next_ray) {
-            let color = next_primitive.material_at_collision(next_col).color;
+    Color {
+        red: intensity * material.color.red,
+        green: intensity * material.color.green,
+        blue: intensity * material.color.blue,
+    }*/
+
+    // Importance sampling
+    let next_dir = to_cosine_directed_hemisphere(collision.normal, sample);
+    let mut next_ray = Ray {
+        pos: collision.pos,
+        dir: next_dir,
+        time: ray.time,
+    };
+    next_ray.move_by(1e-3);
+
+    stats::count_ray(RayKind::Indirect);
+    stats::count_path(2);
+    if let Some((next_primitive, next_col)) = scene.collision(next_ray) {
+        let color = next_primitive.material_at_collision(next_col).color;
 
-            // We hit the light
-            if color.red == 1. && color.blue == 1. && color.green == 0. {
-                // TODO Use the intensity from the light
-                // No need to add a cosine factor due to importance sampling
-                let intensity = 50. / PI;
+        // We hit the light
+        if color.red == 1. && color.blue == 1. && color.green == 0. {
+            // TODO Use the intensity from the light
+            // No need to add a cosine factor due to importance sampling
+            let intensity = 50. / PI;
 
-                assert!(intensity >= 0.);
+            assert!(intensity >= 0.);
 
-                Color {
-                    red: intensity * material.color.red,
-                    green: intensity * material.color.green,
-                    blue: intensity * material.color.blue,
-                }
-            } else {
-                Color::black()
+            Color {
+                red: intensity * material.color.red,
+                green: intensity * material.color.green,
+                blue: intensity * material.color.blue,
             }
         } else {
-            ambient_occlusion
+            Color::black()
         }
     } else {
-        Color::black()
+        ambient_occlusion
     }
 }
 
-fn color<S: Sampler>(
-    ray: Ray,
+/// Returns the information about the first surface hit by a camera ray, used
+/// by the AOVs
+fn first_hit(ray: Ray, index: usize, primitive: &dyn Primitive, collision: Collision) -> FirstHit {
+    FirstHit {
+        albedo: primitive.material_at_collision(collision).color,
+        normal: collision.normal,
+        depth: collision.date * ray.dir.norm(),
+        position: collision.pos,
+        primitive: index,
+    }
+}
+
+/// Returns the samples of a pixel whose indices are in a given range, with
+/// their positions on the film and, if needed by the AOVs, their first hits
+fn samples<S: Sampler>(
+    (x, y): (usize, usize),
     scene: &Scene,
-    nb_samples: usize,
+    indices: Range<usize>,
     sampler: &mut S,
     ambient_occlusion: Color,
-) -> Color {
-    let mut sum = (0., 0., 0.);
+    with_hits: bool,
+) -> Vec<(f64, f64, Color, Option<FirstHit>)> {
+    sampler.start_pixel(x, y);
+
+    indices
+        .map(|index| {
+            sampler.start_sample(index);
+            let film_sample = sampler.next2d();
+            let lens_sample = sampler.next2d();
+            let bounce_sample = sampler.next2d();
+            let time_sample = sampler.next1d();
+
+            let film_x = x as f64 + film_sample[0];
+            let film_y = y as f64 + film_sample[1];
+
+            let ray = scene
+                .camera
+                .generate_ray(film_x, film_y, lens_sample, time_sample);
+
+            let (color, hit) = match ray {
+                Some(ray) => {
+                    let (color, hit) = one_color(ray, scene, bounce_sample, ambient_occlusion);
+                    let hit = hit
+                        .filter(|_| with_hits)
+                        .map(|(index, primitive, collision)| {
+                            first_hit(ray, index, primitive, collision)
+                        });
+                    (color, hit)
+                }
+                None => (Color::black(), None),
+            };
 
-    for _ in 0..nb_samples {
-        sampler.new_sample();
-        let color = one_color(ray, scene, sampler.next2d(), ambient_occlusion);
+            (film_x, film_y, color, hit)
+        })
+        .collect()
+}
 
-        sum.0 += color.red;
-        sum.1 += color.green;
-        sum.2 += color.blue;
+impl<S: Sampler> Renderer for MonteCarloRenderer<S> {
+    fn scheduler(&self) -> Option<&Scheduler> {
+        Some(&self.scheduler)
     }
 
-    let f = 1. / nb_samples as f64;
-    Color::new(f * sum.0, f * sum.1, f * sum.2)
+    fn render(&self, scene: Scene) -> Image {
+        let film = self.render_film(scene);
+        self.develop(&film)
+    }
 }
 
-impl<S: Sampler + 'static> Renderer for MonteCarloRenderer<S> {
-    fn render(&self, scene: Scene) {
-        let scene = Arc::new(scene);
+impl<S: Sampler> MonteCarloRenderer<S> {
+    /// Renders the scene and returns the film, which contains the AOVs
+    pub fn render_film(&self, scene: Scene) -> Film {
+        let film = self.new_film(&scene);
+        self.render_from(scene, Checkpoint::new(film, self.fingerprint()))
+    }
 
-        let width = scene.camera.width;
-        let height = scene.camera.height;
-        let iterations_per_pixel = self.iterations_per_step_count;
+    /// Resumes a render from a checkpoint file, and renders the steps from
+    /// the last one in the checkpoint to steps_count. The renderer must have
+    /// the same sampler, filter, number of samples per step and AOVs as the
+    /// one which saved it, but it can have more steps. The strata of the stratified sampler depend on the
+    /// number of samples, so with more steps its new samples are not
+    /// stratified with the previous ones.
+    pub fn resume_film(&self, scene: Scene, checkpoint: &str) -> Result<Film, CheckpointError> {
+        let checkpoint = Checkpoint::load(checkpoint, self.new_film(&scene), self.fingerprint())?;
+        Ok(self.render_from(scene, checkpoint))
+    }
 
-        // The type of a request is Option<Request>, None ends the thread
-        struct Request {
-            x: usize,
-            y: usize,
-        }
+    /// Same as resume_film, and returns the image, denoised if there is a
+    /// denoiser
+    pub fn resume(&self, scene: Scene, checkpoint: &str) -> Result<Image, CheckpointError> {
+        let film = self.resume_film(scene, checkpoint)?;
+        Ok(self.develop(&film))
+    }
+
+    /// Returns an empty film for the image of the camera of a scene
+    fn new_film(&self, scene: &Scene) -> Film {
+        Film::new(
+            scene.camera.width(),
+            scene.camera.height(),
+            self.filter.clone(),
+        )
+        .with_aovs(&self.film_aovs())
+    }
+
+    /// Returns a value depending on the settings which change the samples,
+    /// saved in the checkpoints
+    fn fingerprint(&self) -> u64 {
+        let text = |text: &str| mix_seed(&text.bytes().map(u64::from).collect::<Vec<_>>());
+
+        mix_seed(&[
+            text(type_name::<S>()),
+            self.sampler.fingerprint(),
+            text(&format!("{:?}", self.filter)),
+            self.iterations_per_step_count as u64,
+        ])
+    }
 
-        struct Answer {
-            sender: usize,
-            x: usize,
-            y: usize,
-            color: Color,
+    fn develop(&self, film: &Film) -> Image {
+        match &self.denoiser {
+            Some(denoiser) => denoiser.denoise(film),
+            None => film.image(),
         }
+    }
 
-        let (tx_main, rx_main) = mpsc::channel::<Answer>();
+    /// Returns the AOVs recorded by the film, including the ones needed by
+    /// the denoiser
+    fn film_aovs(&self) -> Vec<Aov> {
+        let mut aovs = self.aovs.clone();
+        if self.denoiser.is_some() {
+            for aov in Denoiser::aovs() {
+                if !aovs.contains(&aov) {
+                    aovs.push(aov);
+                }
+            }
+        }
 
-        let scene = Arc::new(scene);
+        aovs
+    }
 
-        let mut tx_workers = Vec::new();
-        let mut handles = Vec::new();
+    /// Renders the steps which are not in the state yet
+    fn render_from(&self, scene: Scene, mut state: Checkpoint) -> Film {
+        let (width, height) = (state.film.width(), state.film.height());
+
+        let iterations_per_pixel = self.iterations_per_step_count as usize;
+        let samples_per_pixel = self.steps_count as usize * iterations_per_pixel;
+        let with_hits = !state.film.aovs().is_empty();
+        let ambient_occlusion = self.ambient_occlusion;
+
+        // One sample for the position on the film, one for the lens, one for
+        // the bounce and one for the time
+        let mut sampler = self.sampler.clone();
+        sampler.prepare(1, 3, samples_per_pixel);
+
+        let all_tiles = self.scheduler.tiles(width, height);
+
+        // Samples which remain to be computed
+        let remaining: usize = (state.statistics.iter().zip(&state.converged))
+            .filter(|(_, &converged)| !converged)
+            .map(|(statistics, _)| samples_per_pixel.saturating_sub(statistics.count))
+            .sum();
+        let tracker = self
+            .scheduler
+            .tracker(remaining as u64)
+            .with_time_budget(self.time_budget);
+        let deadline = self.time_budget.map(|budget| Instant::now() + budget);
+
+        for step in state.steps..self.steps_count as usize {
+            let converged = &state.converged;
+            let tiles: Vec<Tile> = all_tiles
+                .iter()
+                .filter(|tile| tile.pixels().any(|(x, y)| !converged[y * width + x]))
+                .copied()
+                .collect();
+
+            let over_budget = deadline.is_some_and(|deadline| Instant::now() >= deadline);
+
+            if tiles.is_empty() || over_budget || self.scheduler.is_cancelled() {
+                break;
+            }
 
-        let workers_count = 4; // TODO choose wisely
+            // Each step computes the next samples of the pixels which have not
+            // converged yet. The index of the first one is the number of
+            // samples the pixel already has.
+            let statistics = &state.statistics;
+            let tiles_samples = self.scheduler.run_until(
+                &tiles,
+                deadline,
+                || sampler.clone(),
+                |sampler, tile| {
+                    let rays = stats::counters().rays();
+                    let mut count = 0;
+                    let tile_samples: Vec<_> = tile
+                        .pixels()
+                        .filter(|&(x, y)| !converged[y * width + x])
+                        .map(|(x, y)| {
+                            let first = statistics[y * width + x].count;
+                            let last = (first + iterations_per_pixel).min(samples_per_pixel);
+                            count += last.saturating_sub(first);
+
+                            let samples = samples(
+                                (x, y),
+                                &scene,
+                                first..last,
+                                sampler,
+                                ambient_occlusion,
+                                with_hits,
+                            );
+                            (y * width + x, samples)
+                        })
+                        .collect();
 
-        for worker_id in 0..workers_count {
-            let scene = Arc::clone(&scene);
-            let (tx_worker, rx_worker) = mpsc::channel::<Option<Request>>();
-            tx_workers.push(tx_worker);
-            let tx_main = tx_main.clone();
+                    tracker.advance(count as u64, stats::counters().rays() - rays);
+                    tile_samples
+                },
+            );
 
-            let ambient_occlusion = self.ambient_occlusion;
-            let mut sampler = (self.sampler_factory)(worker_id);
+            // The samples of a cancelled step are discarded, so that all the
+            // pixels of the returned film have the samples of the same steps
+            if self.scheduler.is_cancelled() {
+                break;
+            }
 
-            handles.push(thread::spawn(move || {
-                while let Some(request) = rx_worker.recv().unwrap() {
-                    sampler.prepare(0, 1, iterations_per_pixel as usize);
+            // A step stopped by the time budget keeps the samples of the
+            // tiles it rendered, and is not complete
+            let complete = tiles_samples.iter().all(Option::is_some);
 
-                    let (x, y) = (request.x, request.y);
+            // The samples are returned in the order of the tiles, so the sums
+            // of the film are exactly the same from one render to another
+            for (index, samples) in tiles_samples.into_iter().flatten().flatten() {
+                for (x, y, color, hit) in samples {
+                    state.statistics[index].add(color.luminance());
+                    state.film.add_sample_with_hit(x, y, color, hit);
+                }
+            }
 
-                    // Compute the ray
-                    let ray = scene.camera.generate_ray(x, y);
+            if let Some(threshold) = self.error_threshold {
+                for (converged, statistics) in state.converged.iter_mut().zip(&state.statistics) {
+                    if !*converged
+                        && statistics.count >= self.min_samples_per_pixel as usize
+                        && statistics.relative_error() < threshold
+                    {
+                        *converged = true;
+
+                        // The remaining samples of the pixel are skipped
+                        let skipped = samples_per_pixel.saturating_sub(statistics.count);
+                        tracker.advance(skipped as u64, 0);
+                    }
+                }
+            }
 
-                    // Get the color
-                    let color = color(
-                        ray,
-                        &scene,
-                        iterations_per_pixel as usize,
-                        &mut sampler,
-                        ambient_occlusion,
-                    );
+            if !complete {
+                break;
+            }
+            state.steps = step + 1;
 
-                    tx_main
-                        .send(Answer {
-                            sender: worker_id,
-                            x,
-                            y,
-                            color,
-                        })
-                        .unwrap();
+            // A checkpoint which cannot be saved does not stop the render,
+            // which can still be resumed from the previous one
+            if let Some(checkpoint) = &self.checkpoint {
+                if let Err(error) = state.save(checkpoint) {
+                    eprintln!("cannot save the checkpoint {checkpoint}: {error}");
                 }
-            }));
+            }
+
+            if let Some(on_step) = &self.on_step {
+                on_step(state.steps, &state.film);
+            }
         }
 
-        // Not a real constraint and simplifies a bit the implementation
-        assert!(workers_count <= height);
+        state.film
+    }
+}
 
-        let mut image = Image::new(scene.camera.width, scene.camera.height);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cameras::PerspectiveCamera;
+    use crate::primitives::GeometricPrimitive;
+    use crate::samplers::SobolSampler;
+    use crate::shapes::Sphere;
+    use crate::{CancellationToken, Material, Progress, Vect};
+    use std::{
+        fs,
+        sync::{Arc, Mutex},
+    };
+
+    /// Returns a sphere on the ground in front of the camera, lit by the
+    /// ambient occlusion
+    fn scene() -> Scene {
+        let mut scene = Scene::new(PerspectiveCamera::new(
+            Vect::new(-5., 0., 0.),
+            Vect::new(0., 0., 0.),
+            Vect::new(0., 0., 1.),
+            40.,
+            8,
+            6,
+        ));
+        scene.add_primitive(GeometricPrimitive::new(
+            Sphere::new(Vect::new(0., 0., 0.), 1.),
+            Material::new(Color::new(0.8, 0.5, 0.2)),
+        ));
+        scene.add_primitive(GeometricPrimitive::new(
+            Sphere::new(Vect::new(0., 0., -101.), 100.),
+            Material::new(Color::new(0.5, 0.5, 0.5)),
+        ));
+        scene
+    }
 
-        //for it in 0..1000 {
-        for step in 0..self.steps_count {
-            println!("Iteration {}...", step * self.iterations_per_step_count);
+    fn builder() -> MonteCarloRendererBuilder<SobolSampler> {
+        MonteCarloRendererBuilder::default()
+            .steps_count(2)
+            .iterations_per_step_count(4)
+            .ambient_occlusion(Color::white())
+            .sampler(SobolSampler::new(1))
+    }
 
-            for x in 0..width {
-                for y in 0..height {
-                    let worker_id = if x == 0 && y < workers_count {
-                        y
-                    } else {
-                        let answer = rx_main.recv().unwrap();
-                        let (x, y) = (answer.x, answer.y);
+    #[test]
+    fn test_reproducible_render() {
+        // The image does not depend on the number of threads
+        let render = |threads_count| {
+            builder()
+                .scheduler(Scheduler::new(threads_count).with_tile_size(4))
+                .build()
+                .unwrap()
+                .render(scene())
+        };
 
-                        let prev_color = image.pixel(x, y);
+        let (first, second) = (render(1), render(3));
+        assert_eq!((first.width(), first.height()), (8, 6));
 
-                        let step = step as f64;
-                        image.set_pixel(
-                            x,
-                            y,
-                            Color::new(
-                                (step * prev_color.red + answer.color.red) / (step + 1.),
-                                (step * prev_color.green + answer.color.green) / (step + 1.),
-                                (step * prev_color.blue + answer.color.blue) / (step + 1.),
-                            ),
-                        );
+        let mut lit_pixels = 0;
+        for x in 0..8 {
+            for y in 0..6 {
+                let (a, b) = (first.pixel(x, y), second.pixel(x, y));
+                assert_eq!([a.red, a.green, a.blue], [b.red, b.green, b.blue]);
+                lit_pixels += (a.red > 0.) as usize;
+            }
+        }
+        assert!(lit_pixels > 0);
+    }
 
-                        answer.sender
-                    };
+    #[test]
+    fn test_adaptive_sampling() {
+        let film = builder()
+            .steps_count(4)
+            .error_threshold(1e-3)
+            .min_samples_per_pixel(4)
+            .aovs(vec![Aov::SampleCount])
+            .build()
+            .unwrap()
+            .render_film(scene());
+        let counts = film.aov_image(Aov::SampleCount);
+
+        // The sky is black and stops after the first step, but not the
+        // sphere
+        assert_eq!(counts.pixel(0, 5).red, 4.);
+        assert_eq!(counts.pixel(4, 3).red, 16.);
+    }
 
-                    tx_workers[worker_id].send(Some(Request { x, y })).unwrap();
-                }
-            }
+    #[test]
+    fn test_one_camera_ray_per_sample() {
+        // The AOVs reuse the first hit of the camera ray
+        let (_, stats) = builder()
+            .aovs(vec![Aov::Albedo, Aov::Depth])
+            .build()
+            .unwrap()
+            .render_with_stats(scene());
+        assert_eq!(stats.camera_rays, 8 * 6 * 8);
+        assert_eq!(stats.paths, stats.camera_rays);
+    }
 
-            for _ in 0..workers_count {
-                let answer = rx_main.recv().unwrap();
-                let (x, y) = (answer.x, answer.y);
-
-                let prev_color = image.pixel(x, y);
-
-                let step = step as f64;
-                image.set_pixel(
-                    x,
-                    y,
-                    Color::new(
-                        (step * prev_color.red + answer.color.red) / (step + 1.),
-                        (step * prev_color.green + answer.color.green) / (step + 1.),
-                        (step * prev_color.blue + answer.color.blue) / (step + 1.),
-                    ),
-                );
+    #[test]
+    fn test_resume_from_checkpoint() {
+        let checkpoint = std::env::temp_dir().join("renderer-test-checkpoint.bin");
+        let checkpoint = checkpoint.to_str().unwrap();
+        let builder = || builder().aovs(vec![Aov::Variance, Aov::SampleCount]);
+
+        // A render of 2 steps, and a render of 1 step resumed to 2 steps
+        let full = builder().build().unwrap().render_film(scene());
+        builder()
+            .steps_count(1)
+            .checkpoint(checkpoint)
+            .build()
+            .unwrap()
+            .render_film(scene());
+        let resumed = builder()
+            .build()
+            .unwrap()
+            .resume_film(scene(), checkpoint)
+            .unwrap();
+
+        // A render with another seed would not get the same samples
+        assert!(matches!(
+            builder()
+                .sampler(SobolSampler::new(2))
+                .build()
+                .unwrap()
+                .resume_film(scene(), checkpoint),
+            Err(CheckpointError::SettingsMismatch)
+        ));
+        fs::remove_file(checkpoint).unwrap();
+
+        for aov in [None, Some(Aov::Variance), Some(Aov::SampleCount)] {
+            let image = |film: &Film| aov.map_or(film.image(), |aov| film.aov_image(aov));
+            let (a, b) = (image(&full), image(&resumed));
+            for x in 0..8 {
+                for y in 0..6 {
+                    let (a, b) = (a.pixel(x, y), b.pixel(x, y));
+                    assert_eq!([a.red, a.green, a.blue], [b.red, b.green, b.blue]);
+                }
             }
-
-            image.export(&format!(
-                "{}/output-{:0>5}.png",
-                self.output_folder,
-                self.iterations_per_step_count * (step + 1)
-            ));
-            image.raw_export(&format!(
-                "{}/raw-output-{:0>5}",
-                self.output_folder,
-                self.iterations_per_step_count * (step + 1)
-            ));
         }
+        assert_eq!(resumed.aov_image(Aov::SampleCount).pixel(4, 3).red, 8.);
+    }
 
-        // End the workers
-        for tx in tx_workers {
-            tx.send(None).unwrap();
+    #[test]
+    fn test_progress_and_cancellation() {
+        let last = Arc::new(Mutex::new(None));
+        let progress = Arc::clone(&last);
+        let scheduler = Scheduler::new(2).with_progress(move |p: &Progress| {
+            *progress.lock().unwrap() = Some((p.fraction, p.rays_per_second));
+        });
+        let steps = Arc::new(Mutex::new(Vec::new()));
+        let on_step = Arc::clone(&steps);
+        builder()
+            .scheduler(scheduler)
+            .aovs(vec![Aov::SampleCount])
+            .on_step(move |step, film: &Film| {
+                let count = film.aov_image(Aov::SampleCount).pixel(4, 3).red;
+                on_step.lock().unwrap().push((step, count));
+            })
+            .build()
+            .unwrap()
+            .render(scene());
+
+        let (fraction, rays_per_second) = last.lock().unwrap().unwrap();
+        assert_eq!(fraction, 1.);
+        assert!(rays_per_second > 0.);
+
+        // The film is given after each step
+        assert_eq!(*steps.lock().unwrap(), [(1, 4.), (2, 8.)]);
+
+        // A step cancelled before all its tiles are rendered is discarded
+        let token = CancellationToken::new();
+        let cancel = token.clone();
+        let scheduler = Scheduler::new(1)
+            .with_tile_size(2)
+            .with_cancellation(token)
+            .with_progress(move |p: &Progress| {
+                if p.fraction > 0.6 {
+                    cancel.cancel();
+                }
+            });
+        let film = builder()
+            .scheduler(scheduler)
+            .aovs(vec![Aov::SampleCount])
+            .build()
+            .unwrap()
+            .render_film(scene());
+        let counts = film.aov_image(Aov::SampleCount);
+        for x in 0..8 {
+            for y in 0..6 {
+                assert_eq!(counts.pixel(x, y).red, 4.);
+            }
         }
 
-        // End for the treads to finish
-        for handle in handles {
-            handle.join().unwrap();
+        // Nothing is rendered once the render is cancelled or the budget spent
+        let token = CancellationToken::new();
+        token.cancel();
+        let cancelled = builder().scheduler(Scheduler::new(2).with_cancellation(token));
+        let over_budget = builder().time_budget(Duration::ZERO);
+
+        for builder in [cancelled, over_budget] {
+            let film = builder
+                .aovs(vec![Aov::SampleCount])
+                .build()
+                .unwrap()
+                .render_film(scene());
+            assert_eq!(film.aov_image(Aov::SampleCount).pixel(4, 3).red, 0.);
         }
 
-        image.export("output.png");
+        // A budget much shorter than a step stops the render in the middle
+        // of the step, keeping the tiles already rendered
+        let film = builder()
+            .steps_count(1)
+            .iterations_per_step_count(4096)
+            .scheduler(Scheduler::new(1).with_tile_size(2))
+            .time_budget(Duration::from_millis(5))
+            .aovs(vec![Aov::SampleCount])
+            .build()
+            .unwrap()
+            .render_film(scene());
+        let counts = film.aov_image(Aov::SampleCount);
+        let counts: Vec<f64> = (0..8)
+            .flat_map(|x| (0..6).map(move |y| (x, y)))
+            .map(|(x, y)| counts.pixel(x, y).red)
+            .collect();
+        assert!(counts.contains(&4096.));
+        assert!(counts.contains(&0.));
     }
 }
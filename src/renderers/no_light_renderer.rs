@@ -1,13 +1,28 @@
 use crate::color::Color;
+use crate::image::Image;
 use crate::ray::Ray;
 use crate::renderer::Renderer;
 use crate::scene::Scene;
+use crate::scheduler::Scheduler;
+use crate::stats::{self, RayKind};
 
 /// This basic renderer ignores all the lights in the scene: the color of a ray
 /// is simply the color of the object.
-pub struct NoLightRenderer {}
+#[derive(Default)]
+pub struct NoLightRenderer {
+    scheduler: Scheduler,
+}
 
 impl NoLightRenderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_scheduler(mut self, scheduler: Scheduler) -> Self {
+        self.scheduler = scheduler;
+        self
+    }
+
     fn color(&self, ray: Ray, scene: &Scene) -> Color {
         if let Some((primitive, collision)) = scene.collision(ray) {
             let material = primitive.material_at_collision(collision);
@@ -19,22 +34,25 @@ impl NoLightRenderer {
 }
 
 impl Renderer for NoLightRenderer {
-    fn render(&self, scene: Scene) {
-        let mut image: Vec<Vec<(u8, u8, u8)>> =
-            vec![vec![(0, 0, 0); scene.camera.height]; scene.camera.width];
+    fn scheduler(&self) -> Option<&Scheduler> {
+        Some(&self.scheduler)
+    }
 
-        for x in 0..scene.camera.width {
-            for y in 0..scene.camera.height {
-                let color = self.color(scene.camera.generate_ray(x, y), &scene);
+    fn render(&self, scene: Scene) -> Image {
+        let (width, height) = (scene.camera.width(), scene.camera.height());
 
-                image[x][y] = (
-                    (255. * color.red) as u8,
-                    (255. * color.green) as u8,
-                    (255. * color.blue) as u8,
-                );
+        self.scheduler.render_image(width, height, |x, y| {
+            match scene
+                .camera
+                .generate_ray(x as f64 + 0.5, y as f64 + 0.5, [0., 0.], 0.5)
+            {
+                Some(ray) => {
+                    stats::count_ray(RayKind::Camera);
+                    stats::count_path(1);
+                    self.color(ray, &scene)
+                }
+                None => Color::black(),
             }
-        }
-
-        //image
+        })
     }
 }
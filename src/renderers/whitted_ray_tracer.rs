@@ -1,14 +1,27 @@
-use std::{
-    sync::{mpsc, Arc},
-    thread,
-    thread::available_parallelism,
-};
-
+use crate::scheduler::Scheduler;
+use crate::stats::{self, RayKind};
 use crate::{Color, Image, Ray, Renderer, Scene};
 
-pub struct WhittedRayTracer {}
+#[derive(Default)]
+pub struct WhittedRayTracer {
+    scheduler: Scheduler,
+}
+
+impl WhittedRayTracer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_scheduler(mut self, scheduler: Scheduler) -> Self {
+        self.scheduler = scheduler;
+        self
+    }
+}
+
+fn color(ray: Ray, scene: &Scene) -> Color {
+    stats::count_ray(RayKind::Camera);
+    stats::count_path(1);
 
-fn color(ray: Ray, scene: &Arc<Scene>) -> Color {
     if let Some((primitive, collision)) = scene.collision(ray) {
         let material = primitive.material_at_collision(collision);
 
@@ -19,9 +32,10 @@ fn color(ray: Ray, scene: &Arc<Scene>) -> Color {
             let to_light = light.pos - collision.pos;
 
             // Check if an object hides the light
-            let mut ray_to_light = Ray::new(collision.pos, to_light);
+            let mut ray_to_light = Ray::new(collision.pos, to_light).with_time(ray.time);
             ray_to_light.move_by(1e-3); // TODO make this value depend on the scene
 
+            stats::count_ray(RayKind::Shadow);
             if scene.collision_date(ray_to_light) >= to_light.norm() {
                 let intensity_light = light.intensity * (ray_to_light.dir() * collision.normal);
 
@@ -42,101 +56,23 @@ fn color(ray: Ray, scene: &Arc<Scene>) -> Color {
 }
 
 impl Renderer for WhittedRayTracer {
-    fn render(&self, scene: Scene) {
-        let scene = Arc::new(scene);
-
-        let width = scene.camera.width;
-        let height = scene.camera.height;
-
-        let mut image = Image::new(width, height);
-
-        // The type of a request is Option<Request>, None ends the thread
-        struct Request {
-            x: usize,
-            y: usize,
-        }
-
-        struct Answer {
-            sender: usize,
-            x: usize,
-            y: usize,
-            color: Color,
-        }
-
-        let (tx_main, rx_main) = mpsc::channel::<Answer>();
-
-        let scene = Arc::new(scene);
-
-        let mut tx_workers = Vec::new();
-        let mut handles = Vec::new();
-
-        let workers_count = available_parallelism().unwrap().get();
-
-        for worker_id in 0..workers_count {
-            let scene = Arc::clone(&scene);
-            let (tx_worker, rx_worker) = mpsc::channel::<Option<Request>>();
-            tx_workers.push(tx_worker);
-            let tx_main = tx_main.clone();
-
-            handles.push(thread::spawn(move || {
-                while let Some(request) = rx_worker.recv().unwrap() {
-                    let (x, y) = (request.x, request.y);
-
-                    // Compute the ray
-                    let ray = scene.camera.generate_ray(x, y);
-
-                    // Get the color
-                    let color = color(ray, &scene);
-
-                    tx_main
-                        .send(Answer {
-                            sender: worker_id,
-                            x,
-                            y,
-                            color,
-                        })
-                        .unwrap();
-                }
-            }));
-        }
-
-        // Not a real constraint and simplifies a bit the implementation
-        assert!(workers_count <= height);
-
-        for x in 0..width {
-            for y in 0..height {
-                let worker_id = if x == 0 && y < workers_count {
-                    y
-                } else {
-                    let answer = rx_main.recv().unwrap();
-                    let (x, y) = (answer.x, answer.y);
-
-                    image.set_pixel(x, y, answer.color);
-
-                    answer.sender
-                };
+    fn scheduler(&self) -> Option<&Scheduler> {
+        Some(&self.scheduler)
+    }
 
-                tx_workers[worker_id].send(Some(Request { x, y })).unwrap();
+    fn render(&self, scene: Scene) -> Image {
+        let (width, height) = (scene.camera.width(), scene.camera.height());
+
+        self.scheduler.render_image(width, height, |x, y| {
+            // The ray goes through the center of the pixel, in the middle of
+            // the shutter interval
+            match scene
+                .camera
+                .generate_ray(x as f64 + 0.5, y as f64 + 0.5, [0., 0.], 0.5)
+            {
+                Some(ray) => color(ray, &scene),
+                None => Color::black(),
             }
-        }
-
-        for _ in 0..workers_count {
-            let answer = rx_main.recv().unwrap();
-            let (x, y) = (answer.x, answer.y);
-
-            image.set_pixel(x, y, answer.color);
-        }
-
-        // End the workers
-        for tx in tx_workers {
-            tx.send(None).unwrap();
-        }
-
-        // End for the treads to finish
-        for handle in handles {
-            handle.join().unwrap();
-        }
-
-        image.export("output.png");
+        })
     }
 }
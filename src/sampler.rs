@@ -1,15 +1,99 @@
-pub trait Sampler: Send {
-    /// Initializes the sampler. It will need to generate a fixed number of samples.
+/// Generates the random values used to compute the samples of the pixels.
+///
+/// The values of a sample must only depend on the seed of the sampler, on the
+/// pixel and on the index of the sample, not on the samples generated before,
+/// so that a render does not depend on how the pixels are spread between the
+/// threads.
+pub trait Sampler: Send + Clone {
+    /// Initializes the sampler. It will need to generate a fixed number of samples per pixel.
     /// Each sample will be composed of at most a fixed number of unique random values and a fixed
     /// number of couples of random values.
     fn prepare(&mut self, nb_1d: usize, nb_2d: usize, nb_samples: usize);
 
-    /// Start generating a new sample
-    fn new_sample(&mut self);
+    /// Starts generating the samples of a pixel
+    fn start_pixel(&mut self, x: usize, y: usize);
+
+    /// Starts generating the sample of a given index in the current pixel, in [0; nb_samples[
+    fn start_sample(&mut self, index: usize);
 
     /// Returns a random value in [0; 1]
     fn next1d(&mut self) -> f64;
 
     /// Returns a two random values in [0; 1]
     fn next2d(&mut self) -> [f64; 2];
+
+    /// Returns a value depending on the settings of the sampler, such as its
+    /// seed. It is saved in the checkpoints of the renders, which can only be
+    /// resumed with the same samples.
+    fn fingerprint(&self) -> u64 {
+        0
+    }
+}
+
+/// Returns a seed depending on all the given values, typically the seed of a
+/// sampler and the coordinates of a pixel
+pub fn mix_seed(values: &[u64]) -> u64 {
+    values.iter().fold(0x9e3779b97f4a7c15, |seed, &value| {
+        // Finalizer of SplitMix64
+        let mut x = (seed ^ value).wrapping_add(0x9e3779b97f4a7c15);
+        x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+        x ^ (x >> 31)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::samplers::*;
+
+    /// Returns the values of a sample, computed by a sampler which has
+    /// already generated the samples of other pixels
+    fn values<S: Sampler>(mut sampler: S, history: &[(usize, usize)]) -> Vec<f64> {
+        sampler.prepare(1, 2, 8);
+
+        for &(x, y) in history {
+            sampler.start_pixel(x, y);
+            for index in 0..8 {
+                sampler.start_sample(index);
+                sampler.next2d();
+                sampler.next1d();
+            }
+        }
+
+        sampler.start_pixel(3, 5);
+        sampler.start_sample(6);
+        let [u, v] = sampler.next2d();
+        let [w, z] = sampler.next2d();
+        // Beyond the prepared dimensions and samples
+        sampler.start_sample(11);
+        let [a, b] = sampler.next2d();
+        let c = sampler.next1d();
+        let d = sampler.next1d();
+
+        vec![u, v, w, z, a, b, c, d]
+    }
+
+    fn check<S: Sampler>(sampler: S) {
+        let reference = values(sampler.clone(), &[]);
+        assert_eq!(reference, values(sampler.clone(), &[(0, 0), (7, 1)]));
+        assert_eq!(reference, values(sampler.clone(), &[(3, 5), (2, 2)]));
+
+        // Another pixel gets other values
+        let mut other = sampler;
+        other.prepare(1, 2, 8);
+        other.start_pixel(5, 3);
+        other.start_sample(6);
+        assert_ne!(reference[..2], other.next2d());
+    }
+
+    #[test]
+    fn test_samples_independent_of_history() {
+        check(IndependentSampler::new(1));
+        check(StratifiedSampler::new(1));
+        check(HaltonSampler::new(1));
+        check(SobolSampler::new(1));
+        check(Pmj02Sampler::new(1));
+        check(BlueNoiseSampler::new(1));
+    }
 }
@@ -0,0 +1,280 @@
+//! Measures of the quality of the samples generated by a sampler, to compare
+//! the samplers with each other: the star discrepancy of the points, the
+//! convergence rate of the integration of known functions, and the
+//! stratification of the points.
+
+use crate::sampler::Sampler;
+use std::f64::consts::PI;
+
+/// Function over [0; 1]^2 whose integral is known
+pub struct Integrand {
+    pub name: &'static str,
+    pub f: fn([f64; 2]) -> f64,
+    pub integral: f64,
+}
+
+/// Integrands with various smoothness
+pub const INTEGRANDS: [Integrand; 3] = [
+    // Discontinuous along a curve
+    Integrand {
+        name: "disk",
+        f: |[x, y]| ((x - 0.5).powi(2) + (y - 0.5).powi(2) < 0.45 * 0.45) as u8 as f64,
+        integral: PI * 0.45 * 0.45,
+    },
+    // Smooth
+    Integrand {
+        name: "gaussian",
+        f: |[x, y]| (-x * x - y * y).exp(),
+        // (sqrt(pi) / 2 * erf(1))^2
+        integral: 0.557_746_285_351_034_1,
+    },
+    // Discontinuous along a line which is not aligned with the axes
+    Integrand {
+        name: "step",
+        f: |[x, y]| (y < x / 3. + 1. / 3.) as u8 as f64,
+        integral: 0.5,
+    },
+];
+
+/// Returns the 2D points given by a sampler in one pixel, for one of the 2D
+/// dimensions of the samples
+pub fn points<S: Sampler>(
+    sampler: &S,
+    pixel: (usize, usize),
+    dimension: usize,
+    nb_samples: usize,
+) -> Vec<[f64; 2]> {
+    let mut sampler = sampler.clone();
+    sampler.prepare(0, dimension + 1, nb_samples);
+    sampler.start_pixel(pixel.0, pixel.1);
+
+    (0..nb_samples)
+        .map(|index| {
+            sampler.start_sample(index);
+            for _ in 0..dimension {
+                sampler.next2d();
+            }
+            sampler.next2d()
+        })
+        .collect()
+}
+
+/// Returns the star discrepancy of points with distinct coordinates: the
+/// largest difference between the area of a box [0; a[ x [0; b[ and the
+/// proportion of the points inside it
+pub fn star_discrepancy(points: &[[f64; 2]]) -> f64 {
+    let n = points.len();
+
+    let mut by_x: Vec<usize> = (0..n).collect();
+    by_x.sort_by(|&i, &j| points[i][0].total_cmp(&points[j][0]));
+
+    let mut by_y: Vec<usize> = (0..n).collect();
+    by_y.sort_by(|&i, &j| points[i][1].total_cmp(&points[j][1]));
+    let mut y_rank = vec![0; n];
+    for (rank, &i) in by_y.iter().enumerate() {
+        y_rank[i] = rank;
+    }
+
+    // The largest difference is reached when a and b are coordinates of the
+    // points or 1, with the boxes either open or closed. The boxes are swept
+    // along x, and inserted[r] tells whether the point of y rank r has a
+    // lower x than the box.
+    let mut inserted = vec![false; n];
+    let mut discrepancy: f64 = 0.;
+
+    for i in 0..=n {
+        let a = by_x.get(i).map_or(1., |&p| points[p][0]);
+
+        let mut below = 0; // Points with x < a and y < b
+        for j in 0..=n {
+            let b = by_y.get(j).map_or(1., |&p| points[p][1]);
+            let on_line = inserted.get(j) == Some(&true);
+            let on_column = i < n && y_rank[by_x[i]] <= j;
+
+            // Open box, then closed box
+            discrepancy = discrepancy.max(a * b - below as f64 / n as f64);
+            let closed = below + on_line as usize + on_column as usize;
+            discrepancy = discrepancy.max(closed as f64 / n as f64 - a * b);
+
+            below += on_line as usize;
+        }
+
+        if i < n {
+            inserted[y_rank[by_x[i]]] = true;
+        }
+    }
+
+    discrepancy
+}
+
+/// Returns the root mean square error of the estimation of the integral of a
+/// function with a number of samples, over several pixels
+pub fn integration_error<S: Sampler>(
+    sampler: &S,
+    integrand: &Integrand,
+    nb_samples: usize,
+    nb_pixels: usize,
+) -> f64 {
+    let squared_errors: f64 = (0..nb_pixels)
+        .map(|pixel| {
+            let points = points(sampler, (pixel, 0), 0, nb_samples);
+            let estimate =
+                points.iter().map(|&p| (integrand.f)(p)).sum::<f64>() / nb_samples as f64;
+            (estimate - integrand.integral).powi(2)
+        })
+        .sum();
+
+    (squared_errors / nb_pixels as f64).sqrt()
+}
+
+/// Returns the exponent r such that the integration error is proportional to
+/// n^r with n samples, estimated with the least squares method over several
+/// numbers of samples. It is -0.5 for independent random samples.
+pub fn convergence_rate<S: Sampler>(
+    sampler: &S,
+    integrand: &Integrand,
+    nb_samples: &[usize],
+    nb_pixels: usize,
+) -> f64 {
+    let logs: Vec<(f64, f64)> = nb_samples
+        .iter()
+        .map(|&n| {
+            let error = integration_error(sampler, integrand, n, nb_pixels);
+            ((n as f64).ln(), error.ln())
+        })
+        .collect();
+
+    let count = logs.len() as f64;
+    let mean_x = logs.iter().map(|l| l.0).sum::<f64>() / count;
+    let mean_y = logs.iter().map(|l| l.1).sum::<f64>() / count;
+    let covariance: f64 = logs.iter().map(|l| (l.0 - mean_x) * (l.1 - mean_y)).sum();
+    let variance: f64 = logs.iter().map(|l| (l.0 - mean_x).powi(2)).sum();
+
+    covariance / variance
+}
+
+/// Tells whether there is the same number of points in each cell of a grid
+/// with a number of columns and lines
+pub fn is_stratified(points: &[[f64; 2]], columns: usize, lines: usize) -> bool {
+    let mut counts = vec![0; columns * lines];
+    for &[x, y] in points {
+        let column = ((x * columns as f64) as usize).min(columns - 1);
+        let line = ((y * lines as f64) as usize).min(lines - 1);
+        counts[line * columns + column] += 1;
+    }
+
+    counts.iter().all(|&count| count == counts[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::samplers::*;
+
+    /// Mean star discrepancy of 256 points over several pixels
+    fn discrepancy<S: Sampler>(sampler: &S, dimension: usize) -> f64 {
+        (0..16)
+            .map(|pixel| star_discrepancy(&points(sampler, (pixel, 0), dimension, 256)))
+            .sum::<f64>()
+            / 16.
+    }
+
+    fn rate<S: Sampler>(sampler: &S, integrand: &Integrand) -> f64 {
+        convergence_rate(sampler, integrand, &[16, 32, 64, 128, 256], 256)
+    }
+
+    #[test]
+    fn test_star_discrepancy() {
+        // Regular grid, whose discrepancy is 1 - (1 - 1 / (2n))^2 for n^2
+        // points
+        let grid: Vec<[f64; 2]> = (0..16)
+            .flat_map(|i| (0..16).map(move |j| [(i as f64 + 0.5) / 16., (j as f64 + 0.5) / 16.]))
+            .collect();
+        let d = star_discrepancy(&grid);
+        assert!((d - (1. - (1. - 1. / 32_f64).powi(2))).abs() < 1e-6);
+
+        let independent = discrepancy(&IndependentSampler::new(0), 0);
+
+        for dimension in [0, 2] {
+            assert!(discrepancy(&StratifiedSampler::new(0), dimension) < 0.5 * independent);
+            assert!(discrepancy(&HaltonSampler::new(0), dimension) < 0.5 * independent);
+            assert!(discrepancy(&SobolSampler::new(0), dimension) < 0.5 * independent);
+            assert!(discrepancy(&Pmj02Sampler::new(0), dimension) < 0.5 * independent);
+            assert!(discrepancy(&BlueNoiseSampler::new(0), dimension) < 0.5 * independent);
+        }
+    }
+
+    #[test]
+    fn test_convergence() {
+        for integrand in &INTEGRANDS {
+            let independent = rate(&IndependentSampler::new(0), integrand);
+            assert!((-0.6..-0.4).contains(&independent));
+
+            // The error of stratified samples decreases as n^-0.75 for
+            // discontinuous functions, and faster for smooth ones
+            let expected = if integrand.name == "gaussian" {
+                -0.9
+            } else {
+                -0.62
+            };
+            assert!(rate(&StratifiedSampler::new(0), integrand) < expected);
+            assert!(rate(&HaltonSampler::new(0), integrand) < expected);
+            assert!(rate(&SobolSampler::new(0), integrand) < expected);
+            assert!(rate(&Pmj02Sampler::new(0), integrand) < expected);
+            assert!(rate(&BlueNoiseSampler::new(0), integrand) < expected);
+        }
+    }
+
+    #[test]
+    fn test_stratification() {
+        // All the elementary intervals of the (0, 2)-sequences
+        for log_columns in 0..=6 {
+            let (columns, lines) = (1 << log_columns, 1 << (6 - log_columns));
+            for dimension in [0, 2] {
+                let sobol = points(&SobolSampler::new(0), (1, 2), dimension, 64);
+                assert!(is_stratified(&sobol, columns, lines));
+                let pmj02 = points(&Pmj02Sampler::new(0), (1, 2), dimension, 64);
+                assert!(is_stratified(&pmj02, columns, lines));
+            }
+        }
+
+        // Grid and projections on the axes
+        let stratified = points(&StratifiedSampler::new(0), (1, 2), 1, 48);
+        for (columns, lines) in [(6, 8), (48, 1), (1, 48)] {
+            assert!(is_stratified(&stratified, columns, lines));
+        }
+
+        // Bases 2 and 3 for the first dimension
+        let halton = points(&HaltonSampler::new(0), (1, 2), 0, 72);
+        for (columns, lines) in [(8, 1), (1, 9), (8, 9)] {
+            assert!(is_stratified(&halton, columns, lines));
+        }
+
+        assert!(!is_stratified(
+            &points(&IndependentSampler::new(0), (1, 2), 0, 64),
+            8,
+            8
+        ));
+    }
+
+    /// Prints the measures for all the samplers. Run with
+    /// cargo test --release sampler_report -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn sampler_report() {
+        fn report<S: Sampler>(name: &str, sampler: S) {
+            print!("{name:>12}  discrepancy {:.4}", discrepancy(&sampler, 0));
+            for integrand in &INTEGRANDS {
+                print!("  {} {:.2}", integrand.name, rate(&sampler, integrand));
+            }
+            println!();
+        }
+
+        report("independent", IndependentSampler::new(0));
+        report("stratified", StratifiedSampler::new(0));
+        report("halton", HaltonSampler::new(0));
+        report("sobol", SobolSampler::new(0));
+        report("pmj02", Pmj02Sampler::new(0));
+        report("blue noise", BlueNoiseSampler::new(0));
+    }
+}
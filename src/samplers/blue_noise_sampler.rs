@@ -0,0 +1,142 @@
+use crate::{
+    blue_noise::{blue_noise, TILE_SIZE},
+    sampler::{mix_seed, Sampler},
+};
+use fastrand::Rng;
+
+/// Samples distributing the error as blue noise over the image. All the pixels
+/// use the same Kronecker sequence (the R_d sequence of Roberts, which is
+/// well spread in any number of dimensions), shifted in each pixel by the
+/// values of a blue-noise tile. Neighbouring pixels thus get very different
+/// samples, which mostly removes the low-frequency noise at low sample
+/// counts.
+///
+/// The shifts only depend on the seed: all the samplers used to render an
+/// image must have the same seed, otherwise the noise is not blue anymore.
+#[derive(Clone)]
+pub struct BlueNoiseSampler {
+    seed: u64,
+
+    // Used for the values beyond the prepared dimensions
+    rng: Rng,
+
+    // Increment of the sequence along each coordinate
+    alphas: Vec<f64>,
+
+    // Position of the tile used by each coordinate, so that the coordinates
+    // are shifted independently
+    tile_offsets: Vec<(usize, usize)>,
+
+    pixel: (usize, usize),
+    current_sample: usize,
+    current_coordinate: usize,
+}
+
+impl BlueNoiseSampler {
+    pub fn new(seed: u64) -> Self {
+        BlueNoiseSampler {
+            seed,
+            rng: Rng::with_seed(seed),
+            alphas: Vec::new(),
+            tile_offsets: Vec::new(),
+            pixel: (0, 0),
+            current_sample: 0,
+            current_coordinate: 0,
+        }
+    }
+
+    fn next_coordinate(&mut self) -> f64 {
+        let coordinate = self.current_coordinate;
+        self.current_coordinate += 1;
+
+        // Beyond the prepared dimensions
+        if coordinate >= self.alphas.len() {
+            return self.rng.f64();
+        }
+
+        let (dx, dy) = self.tile_offsets[coordinate];
+        let shift = blue_noise(self.pixel.0 + dx, self.pixel.1 + dy);
+
+        (0.5 + self.current_sample as f64 * self.alphas[coordinate] + shift).fract()
+    }
+}
+
+impl Sampler for BlueNoiseSampler {
+    fn prepare(&mut self, nb_1d: usize, nb_2d: usize, _nb_samples: usize) {
+        let dimensions = nb_1d + 2 * nb_2d;
+
+        // The generalized golden ratio phi is the positive root of
+        // x^(d + 1) = x + 1, and the increments are the powers of 1 / phi
+        let mut phi: f64 = 2.;
+        for _ in 0..50 {
+            phi = (1. + phi).powf(1. / (dimensions as f64 + 1.));
+        }
+        self.alphas = (1..=dimensions)
+            .map(|k| (1. / phi.powi(k as i32)).fract())
+            .collect();
+
+        let mut rng = Rng::with_seed(self.seed);
+        self.tile_offsets = (0..dimensions)
+            .map(|_| (rng.usize(..TILE_SIZE), rng.usize(..TILE_SIZE)))
+            .collect();
+    }
+
+    fn start_pixel(&mut self, x: usize, y: usize) {
+        self.pixel = (x, y);
+    }
+
+    fn start_sample(&mut self, index: usize) {
+        let (x, y) = self.pixel;
+        self.rng = Rng::with_seed(mix_seed(&[self.seed, x as u64, y as u64, index as u64]));
+
+        self.current_sample = index;
+        self.current_coordinate = 0;
+    }
+
+    fn next1d(&mut self) -> f64 {
+        self.next_coordinate()
+    }
+
+    fn next2d(&mut self) -> [f64; 2] {
+        [self.next_coordinate(), self.next_coordinate()]
+    }
+
+    fn fingerprint(&self) -> u64 {
+        mix_seed(&[self.seed])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_neighbouring_pixels() {
+        let mut sampler = BlueNoiseSampler::new(2);
+        sampler.prepare(1, 1, 1);
+
+        // With one sample per pixel, the values of neighbouring pixels are
+        // further apart than with white noise, for which the mean difference
+        // would be 1/3
+        let mut value = |x, y| {
+            sampler.start_pixel(x, y);
+            sampler.start_sample(0);
+            let [u, v] = sampler.next2d();
+            let w = sampler.next1d();
+            [u, v, w]
+        };
+
+        let mut difference = [0.; 3];
+        for y in 0..32 {
+            for x in 0..32 {
+                let (a, b) = (value(x, y), value(x + 1, y));
+                for i in 0..3 {
+                    assert!((0. ..1.).contains(&a[i]));
+                    difference[i] += (a[i] - b[i]).abs() / (32. * 32.);
+                }
+            }
+        }
+
+        assert!(difference.iter().all(|&d| d > 0.36));
+    }
+}
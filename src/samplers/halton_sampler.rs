@@ -0,0 +1,154 @@
+use crate::sampler::{mix_seed, Sampler};
+use fastrand::Rng;
+
+/// Largest f64 lower than 1
+const ONE_MINUS_EPSILON: f64 = 1. - f64::EPSILON / 2.;
+
+/// Samples of the Halton sequence: the dimension d of the sample i is the
+/// radical inverse of i in the base given by the d-th prime number. The digits
+/// are scrambled by random permutations, which are drawn again for each pixel.
+#[derive(Clone)]
+pub struct HaltonSampler {
+    seed: u64,
+    rng: Rng,
+
+    // Bases of the dimensions
+    primes: Vec<usize>,
+
+    // Random permutations of the digits, for each dimension and each digit.
+    // They are generated when a dimension is used for the first time.
+    permutations: Vec<Vec<Vec<u16>>>,
+
+    current_sample: usize,
+    current_dim: usize,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> Self {
+        HaltonSampler {
+            seed,
+            rng: Rng::with_seed(seed),
+            primes: Vec::new(),
+            permutations: Vec::new(),
+            current_sample: 0,
+            current_dim: 0,
+        }
+    }
+
+    /// Returns the base of a dimension
+    fn prime(&mut self, dim: usize) -> usize {
+        while self.primes.len() <= dim {
+            let mut candidate = self.primes.last().map_or(2, |p| p + 1);
+            while self
+                .primes
+                .iter()
+                .take_while(|&&p| p * p <= candidate)
+                .any(|&p| candidate.is_multiple_of(p))
+            {
+                candidate += 1;
+            }
+            self.primes.push(candidate);
+        }
+
+        self.primes[dim]
+    }
+
+    /// Returns the scrambled radical inverse of the current sample in the
+    /// base of a dimension
+    fn radical_inverse(&mut self, dim: usize) -> f64 {
+        let base = self.prime(dim);
+
+        while self.permutations.len() <= dim {
+            let base = self.prime(self.permutations.len());
+
+            // Enough digits to reach the precision of a f64. The leading zeros
+            // of the index are permuted too.
+            let digits_count = (52. / (base as f64).log2()).ceil() as usize;
+            let permutations = (0..digits_count)
+                .map(|_| {
+                    let mut permutation: Vec<u16> = (0..base as u16).collect();
+                    self.rng.shuffle(&mut permutation);
+                    permutation
+                })
+                .collect();
+
+            self.permutations.push(permutations);
+        }
+
+        let mut index = self.current_sample;
+        let mut value = 0.;
+        let mut inv_base_power = 1. / base as f64;
+
+        for permutation in &self.permutations[dim] {
+            let digit = index % base;
+            value += permutation[digit] as f64 * inv_base_power;
+            index /= base;
+            inv_base_power /= base as f64;
+        }
+
+        value.min(ONE_MINUS_EPSILON)
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn prepare(&mut self, _nb_1d: usize, _nb_2d: usize, _nb_samples: usize) {}
+
+    fn start_pixel(&mut self, x: usize, y: usize) {
+        // New permutations, so that the pixels are independent
+        self.rng = Rng::with_seed(mix_seed(&[self.seed, x as u64, y as u64]));
+        self.permutations.clear();
+    }
+
+    fn start_sample(&mut self, index: usize) {
+        self.current_sample = index;
+        self.current_dim = 0;
+    }
+
+    fn next1d(&mut self) -> f64 {
+        self.current_dim += 1;
+        self.radical_inverse(self.current_dim - 1)
+    }
+
+    fn next2d(&mut self) -> [f64; 2] {
+        self.current_dim += 2;
+        [
+            self.radical_inverse(self.current_dim - 2),
+            self.radical_inverse(self.current_dim - 1),
+        ]
+    }
+
+    fn fingerprint(&self) -> u64 {
+        mix_seed(&[self.seed])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stratification() {
+        let mut sampler = HaltonSampler::new(7);
+        sampler.prepare(1, 1, 54);
+        sampler.start_pixel(0, 0);
+
+        // With 2 * 27 samples, the dimensions in base 2 and in base 3 are
+        // stratified
+        let mut strata_2 = [0; 2];
+        let mut strata_3 = [0; 27];
+        for index in 0..54 {
+            sampler.start_sample(index);
+            let [u, v] = sampler.next2d();
+            let w = sampler.next1d();
+
+            assert!((0. ..1.).contains(&u) && (0. ..1.).contains(&v));
+            assert!((0. ..1.).contains(&w));
+
+            strata_2[(2. * u) as usize] += 1;
+            strata_3[(27. * v) as usize] += 1;
+        }
+
+        assert!(strata_2.iter().all(|&count| count == 27));
+        assert!(strata_3.iter().all(|&count| count == 2));
+    }
+}
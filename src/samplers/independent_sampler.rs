@@ -1,22 +1,34 @@
-use crate::sampler::Sampler;
+use crate::sampler::{mix_seed, Sampler};
 use fastrand::Rng;
 
+#[derive(Clone)]
 pub struct IndependentSampler {
+    seed: u64,
+    pixel: (usize, usize),
     rng: Rng,
 }
 
 impl IndependentSampler {
     pub fn new(seed: u64) -> Self {
         IndependentSampler {
+            seed,
+            pixel: (0, 0),
             rng: fastrand::Rng::with_seed(seed),
         }
     }
 }
 
 impl Sampler for IndependentSampler {
-    fn prepare(&mut self, nb_1d: usize, nb_2d: usize, nb_samples: usize) {}
+    fn prepare(&mut self, _nb_1d: usize, _nb_2d: usize, _nb_samples: usize) {}
 
-    fn new_sample(&mut self) {}
+    fn start_pixel(&mut self, x: usize, y: usize) {
+        self.pixel = (x, y);
+    }
+
+    fn start_sample(&mut self, index: usize) {
+        let (x, y) = self.pixel;
+        self.rng = Rng::with_seed(mix_seed(&[self.seed, x as u64, y as u64, index as u64]));
+    }
 
     fn next1d(&mut self) -> f64 {
         self.rng.f64()
@@ -25,4 +37,8 @@ impl Sampler for IndependentSampler {
     fn next2d(&mut self) -> [f64; 2] {
         [self.rng.f64(), self.rng.f64()]
     }
+
+    fn fingerprint(&self) -> u64 {
+        mix_seed(&[self.seed])
+    }
 }
@@ -1,5 +1,13 @@
+pub mod blue_noise_sampler;
+pub mod halton_sampler;
 pub mod independent_sampler;
+pub mod pmj02_sampler;
+pub mod sobol_sampler;
 pub mod stratified_sampler;
 
+pub use blue_noise_sampler::BlueNoiseSampler;
+pub use halton_sampler::HaltonSampler;
 pub use independent_sampler::IndependentSampler;
+pub use pmj02_sampler::Pmj02Sampler;
+pub use sobol_sampler::SobolSampler;
 pub use stratified_sampler::StratifiedSampler;
@@ -0,0 +1,292 @@
+use crate::sampler::{mix_seed, Sampler};
+use fastrand::Rng;
+
+/// Progressive multi-jittered (0, 2) samples (Christensen, Kensler and
+/// Kilpatrick, 2018). Every power of two of consecutive samples is stratified
+/// in all the rectangles of the same area, and the new samples are placed
+/// in the subquadrants diagonally opposite to the previous ones.
+///
+/// The sequence is generated once and shared by all the pixels. For each
+/// pixel, every dimension uses it with a random digital shift, which keeps the
+/// stratification, and the samples are shuffled between the dimensions.
+#[derive(Clone)]
+pub struct Pmj02Sampler {
+    seed: u64,
+
+    // Only used to generate the sequence, so that its points do not depend
+    // on the pixels rendered before it is extended
+    sequence_rng: Rng,
+
+    // Used to randomize the dimensions of the current pixel
+    pixel_rng: Rng,
+
+    // Points of the sequence, in fixed point
+    points: Vec<[u32; 2]>,
+
+    // Randomization of the dimensions of the current pixel
+    dimensions: Vec<Dimension>,
+    nb_samples: usize,
+
+    current_sample: usize,
+    current_dim: usize,
+}
+
+#[derive(Clone)]
+struct Dimension {
+    shift: [u32; 2],
+
+    // Index of the point used by each sample
+    permutation: Vec<usize>,
+}
+
+impl Pmj02Sampler {
+    pub fn new(seed: u64) -> Self {
+        Pmj02Sampler {
+            seed,
+            sequence_rng: Rng::with_seed(seed),
+            pixel_rng: Rng::with_seed(seed),
+            points: Vec::new(),
+            dimensions: Vec::new(),
+            nb_samples: 0,
+            current_sample: 0,
+            current_dim: 0,
+        }
+    }
+
+    /// Generates the points of the sequence until there are at least count
+    /// of them
+    fn extend(&mut self, count: usize) {
+        if self.points.is_empty() {
+            self.points
+                .push([self.sequence_rng.u32(..), self.sequence_rng.u32(..)]);
+        }
+
+        while self.points.len() < count {
+            self.double();
+        }
+    }
+
+    /// Doubles the number of points of the sequence
+    fn double(&mut self) {
+        let n = self.points.len();
+        let m = n.trailing_zeros() as usize;
+        // Number of bits of the strata once the points are added
+        let k = m + 1;
+        assert!(k <= 32);
+
+        // occupied[a] tells which elementary intervals with 2^a columns and
+        // 2^(k-a) lines contain a point
+        let mut occupied = vec![vec![false; 2 * n]; k + 1];
+        let mark = |occupied: &mut Vec<Vec<bool>>, [x, y]: [u32; 2]| {
+            for (a, intervals) in occupied.iter_mut().enumerate() {
+                intervals[(prefix(x, a) << (k - a)) | prefix(y, k - a)] = true;
+            }
+        };
+        for &point in &self.points {
+            mark(&mut occupied, point);
+        }
+
+        // If there is a power of 4 of points, the new point is in the
+        // subquadrant diagonally opposite to the old point, in the grid
+        // containing one point per cell. Otherwise, it is in the same cell as
+        // the old point, in one of the two empty subquadrants.
+        let (prefix_len, flip) = if m.is_multiple_of(2) {
+            (m / 2 + 1, 1)
+        } else {
+            ((m - 1) / 2, 0)
+        };
+
+        for i in 0..n {
+            let [old_x, old_y] = self.points[i];
+            let x_prefix = prefix(old_x, prefix_len) ^ flip;
+            let y_prefix = prefix(old_y, prefix_len) ^ flip;
+
+            let strata = self
+                .find_strata(&occupied, k, (x_prefix, y_prefix), prefix_len)
+                // Should not happen, but only the stratification matters
+                .or_else(|| self.find_strata(&occupied, k, (0, 0), 0));
+
+            let point = match strata {
+                Some((x, y)) => [self.jitter(x, k), self.jitter(y, k)],
+                None => [self.sequence_rng.u32(..), self.sequence_rng.u32(..)],
+            };
+
+            mark(&mut occupied, point);
+            self.points.push(point);
+        }
+    }
+
+    /// Returns the column and the line of a free cell, in the grid with 2^k
+    /// columns and lines, such that all the elementary intervals containing
+    /// it are free. The prefixes give the first bits of the column and of
+    /// the line.
+    fn find_strata(
+        &mut self,
+        occupied: &[Vec<bool>],
+        k: usize,
+        (x_prefix, y_prefix): (usize, usize),
+        prefix_len: usize,
+    ) -> Option<(usize, usize)> {
+        let free_bits = k - prefix_len;
+        let mut columns: Vec<usize> = (0..1 << free_bits)
+            .map(|suffix| (x_prefix << free_bits) | suffix)
+            .filter(|&x| !occupied[k][x])
+            .collect();
+        self.sequence_rng.shuffle(&mut columns);
+
+        for x in columns {
+            if let Some(y) = self.find_line(occupied, k, x, y_prefix, prefix_len, 0, 0) {
+                return Some((x, y));
+            }
+        }
+
+        None
+    }
+
+    /// Chooses the bits of the line one by one, from the most significant
+    /// one. When the line has d bits, its intersection with the elementary
+    /// intervals having 2^d lines must be free.
+    #[allow(clippy::too_many_arguments)]
+    fn find_line(
+        &mut self,
+        occupied: &[Vec<bool>],
+        k: usize,
+        x: usize,
+        y_prefix: usize,
+        prefix_len: usize,
+        y: usize,
+        d: usize,
+    ) -> Option<usize> {
+        if occupied[k - d][((x >> d) << d) | y] {
+            return None;
+        }
+        if d == k {
+            return Some(y);
+        }
+
+        let bits = if d < prefix_len {
+            let bit = (y_prefix >> (prefix_len - d - 1)) & 1;
+            [Some(bit), None]
+        } else {
+            let bit = self.sequence_rng.usize(..2);
+            [Some(bit), Some(1 - bit)]
+        };
+
+        bits.into_iter().flatten().find_map(|bit| {
+            self.find_line(occupied, k, x, y_prefix, prefix_len, 2 * y + bit, d + 1)
+        })
+    }
+
+    /// Returns a random position in a stratum of size 2^-k, in fixed point
+    fn jitter(&mut self, stratum: usize, k: usize) -> u32 {
+        let position = ((stratum as u64) << (32 - k)) | (self.sequence_rng.u32(..) as u64 >> k);
+        position as u32
+    }
+
+    fn next_point(&mut self) -> [u32; 2] {
+        if self.current_dim >= self.dimensions.len() {
+            // The first dimension is not shuffled, to keep the samples in the
+            // progressive order
+            let mut permutation: Vec<usize> = (0..self.nb_samples).collect();
+            if self.current_dim > 0 {
+                self.pixel_rng.shuffle(&mut permutation);
+            }
+
+            self.dimensions.push(Dimension {
+                shift: [self.pixel_rng.u32(..), self.pixel_rng.u32(..)],
+                permutation,
+            });
+        }
+
+        let dimension = &self.dimensions[self.current_dim];
+        self.current_dim += 1;
+
+        // Samples beyond the prepared count are not shuffled
+        let index = dimension
+            .permutation
+            .get(self.current_sample)
+            .copied()
+            .unwrap_or(self.current_sample);
+        let shift = dimension.shift;
+
+        self.extend(index + 1);
+        let [x, y] = self.points[index];
+
+        [x ^ shift[0], y ^ shift[1]]
+    }
+}
+
+/// Returns the first bits of a number in fixed point
+fn prefix(x: u32, bits: usize) -> usize {
+    ((x as u64) >> (32 - bits)) as usize
+}
+
+fn to_f64(x: u32) -> f64 {
+    x as f64 / (1u64 << 32) as f64
+}
+
+impl Sampler for Pmj02Sampler {
+    fn prepare(&mut self, _nb_1d: usize, _nb_2d: usize, nb_samples: usize) {
+        self.extend(nb_samples);
+        self.nb_samples = nb_samples;
+    }
+
+    fn start_pixel(&mut self, x: usize, y: usize) {
+        self.pixel_rng = Rng::with_seed(mix_seed(&[self.seed, x as u64, y as u64]));
+        self.dimensions.clear();
+    }
+
+    fn start_sample(&mut self, index: usize) {
+        self.current_sample = index;
+        self.current_dim = 0;
+    }
+
+    fn next1d(&mut self) -> f64 {
+        to_f64(self.next_point()[0])
+    }
+
+    fn next2d(&mut self) -> [f64; 2] {
+        let [x, y] = self.next_point();
+        [to_f64(x), to_f64(y)]
+    }
+
+    fn fingerprint(&self) -> u64 {
+        mix_seed(&[self.seed])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progressive_elementary_intervals() {
+        let mut sampler = Pmj02Sampler::new(5);
+        sampler.prepare(0, 2, 256);
+        sampler.start_pixel(0, 0);
+
+        let mut points = vec![Vec::new(); 2];
+        for index in 0..256 {
+            sampler.start_sample(index);
+            for dim in points.iter_mut() {
+                dim.push(sampler.next2d());
+            }
+        }
+
+        // The first dimension is progressive: every power of two of samples
+        // is a (0, 2)-net. The other ones are shuffled.
+        for (dim, counts) in points.iter().zip([1, 256]) {
+            for count in (0..=8).map(|k| 1 << k).filter(|&c: &usize| c >= counts) {
+                let log_count = count.trailing_zeros();
+                for log_x in 0..=log_count {
+                    let (nx, ny) = (1 << log_x, 1 << (log_count - log_x));
+                    let mut cells = vec![0; count];
+                    for [x, y] in &dim[..count] {
+                        cells[(x * nx as f64) as usize * ny + (y * ny as f64) as usize] += 1;
+                    }
+                    assert!(cells.iter().all(|&c| c == 1));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use crate::sampler::{mix_seed, Sampler};
+
+/// Samples built from the first two dimensions of the Sobol sequence, which
+/// form a (0, 2)-sequence: every power of two of consecutive samples is
+/// stratified in all the rectangles of the same area. Each 1D or 2D dimension
+/// uses these points with an independent Owen scrambling and an independent
+/// shuffling of the samples, computed with the hashing method of Burley
+/// (2020). The scrambling is drawn again for each pixel.
+#[derive(Clone)]
+pub struct SobolSampler {
+    seed: u64,
+
+    // Seed of the scrambling of the current pixel
+    pixel_seed: u32,
+
+    current_sample: u32,
+    current_dim: u32,
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64) -> Self {
+        SobolSampler {
+            seed,
+            pixel_seed: 0,
+            current_sample: 0,
+            current_dim: 0,
+        }
+    }
+
+    /// Returns the point of the current sample in the current dimension, in
+    /// fixed point
+    fn next_point(&mut self) -> [u32; 2] {
+        let seed = hash(self.pixel_seed, self.current_dim);
+        self.current_dim += 1;
+
+        // Shuffle the samples, without breaking the stratification of the
+        // powers of two
+        let index = nested_uniform_scramble(self.current_sample, hash(seed, 0));
+
+        [
+            nested_uniform_scramble(index.reverse_bits(), hash(seed, 1)),
+            nested_uniform_scramble(sobol_dim_1(index), hash(seed, 2)),
+        ]
+    }
+}
+
+/// Returns the second dimension of the Sobol sequence, in fixed point (the
+/// first one is the reversed index)
+fn sobol_dim_1(mut index: u32) -> u32 {
+    let mut result = 0;
+    let mut direction = 1 << 31;
+
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= direction;
+        }
+        index >>= 1;
+        direction ^= direction >> 1;
+    }
+
+    result
+}
+
+/// Owen scrambling of a number in fixed point, where each bit is flipped
+/// depending on the bits above it
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+/// Hash function where each bit only depends on the bits below it
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+    x
+}
+
+fn hash(a: u32, b: u32) -> u32 {
+    let mut x = a ^ b.wrapping_mul(0x9e3779b9);
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846ca68b);
+    x ^= x >> 16;
+    x
+}
+
+fn to_f64(x: u32) -> f64 {
+    x as f64 / (1u64 << 32) as f64
+}
+
+impl Sampler for SobolSampler {
+    fn prepare(&mut self, _nb_1d: usize, _nb_2d: usize, _nb_samples: usize) {}
+
+    fn start_pixel(&mut self, x: usize, y: usize) {
+        self.pixel_seed = mix_seed(&[self.seed, x as u64, y as u64]) as u32;
+    }
+
+    fn start_sample(&mut self, index: usize) {
+        self.current_sample = index as u32;
+        self.current_dim = 0;
+    }
+
+    fn next1d(&mut self) -> f64 {
+        to_f64(self.next_point()[0])
+    }
+
+    fn next2d(&mut self) -> [f64; 2] {
+        let [x, y] = self.next_point();
+        [to_f64(x), to_f64(y)]
+    }
+
+    fn fingerprint(&self) -> u64 {
+        mix_seed(&[self.seed])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_elementary_intervals() {
+        let mut sampler = SobolSampler::new(3);
+        sampler.prepare(0, 3, 64);
+        sampler.start_pixel(0, 0);
+
+        let mut points = vec![Vec::new(); 3];
+        for index in 0..64 {
+            sampler.start_sample(index);
+            for dim in points.iter_mut() {
+                dim.push(sampler.next2d());
+            }
+        }
+
+        // In every dimension, each elementary interval of area 1/64 contains
+        // exactly one point
+        for dim in &points {
+            for log_x in 0..=6 {
+                let (nx, ny) = (1 << log_x, 1 << (6 - log_x));
+                let mut counts = vec![0; 64];
+                for [x, y] in dim {
+                    let cell = (x * nx as f64) as usize * ny + (y * ny as f64) as usize;
+                    counts[cell] += 1;
+                }
+                assert!(counts.iter().all(|&count| count == 1));
+            }
+        }
+    }
+}
@@ -1,84 +1,216 @@
-use crate::sampler::Sampler;
+use crate::sampler::{mix_seed, Sampler};
 use fastrand::Rng;
 
+/// Stratified samples: in each dimension, the samples are spread over strata
+/// of equal size, and the strata are shuffled independently between the
+/// dimensions. The values requested beyond the prepared dimensions or samples
+/// are independent random values.
+#[derive(Clone)]
 pub struct StratifiedSampler {
+    seed: u64,
+    pixel: (usize, usize),
     rng: Rng,
     with_jittering: bool,
-    samples_2d: Vec<Vec<[f64; 2]>>, // (nb_samples, nb_2d)
+    samples_1d: Vec<Vec<f64>>,      // (nb_1d, nb_samples)
+    samples_2d: Vec<Vec<[f64; 2]>>, // (nb_2d, nb_samples)
+    nb_1d: usize,
+    nb_2d: usize,
+    nb_samples: usize,
     current_sample: usize,
+    current_1d_dim: usize,
     current_2d_dim: usize,
 }
 
 impl StratifiedSampler {
     pub fn new(seed: u64) -> Self {
         StratifiedSampler {
+            seed,
+            pixel: (0, 0),
             rng: Rng::with_seed(seed),
             with_jittering: true,
+            samples_1d: Vec::new(),
             samples_2d: Vec::new(),
+            nb_1d: 0,
+            nb_2d: 0,
+            nb_samples: 0,
             current_sample: 0,
+            current_1d_dim: 0,
             current_2d_dim: 0,
         }
     }
 
     pub fn new_without_jittering(seed: u64) -> Self {
         StratifiedSampler {
-            rng: fastrand::Rng::with_seed(seed),
             with_jittering: false,
-            samples_2d: Vec::new(),
-            current_sample: 0,
-            current_2d_dim: 0,
+            ..Self::new(seed)
+        }
+    }
+
+    /// Returns the position of a sample in its stratum
+    fn offset(&mut self) -> f64 {
+        if self.with_jittering {
+            self.rng.f64()
+        } else {
+            // Not 0.5 to prevent rays from being parallel to the walls
+            // TODO 0.5
+            0.501
         }
     }
+
+    /// Returns one stratified value per sample, in a random order
+    fn strata_1d(&mut self, nb_samples: usize) -> Vec<f64> {
+        let mut strata: Vec<f64> = (0..nb_samples)
+            .map(|i| (i as f64 + self.offset()) / nb_samples as f64)
+            .collect();
+        self.rng.shuffle(&mut strata);
+        strata
+    }
+
+    /// Returns one stratified point per sample, in a random order. The
+    /// points are spread over a grid as square as possible, and each of them
+    /// is also alone in its column and in its line among nb_samples columns
+    /// and lines (multi-jittering), which keeps a good stratification on each
+    /// axis when the number of samples has no good factorization.
+    fn strata_2d(&mut self, nb_samples: usize) -> Vec<[f64; 2]> {
+        // The grid has nx columns and ny lines
+        let nx = (1..=(nb_samples as f64).sqrt() as usize)
+            .rev()
+            .find(|d| nb_samples.is_multiple_of(*d))
+            .unwrap_or(1);
+        let ny = nb_samples / nx;
+
+        // Canonical arrangement, where the points of a cell of the grid are
+        // placed in distinct sub-columns and sub-lines
+        let mut points = vec![[0.; 2]; nb_samples];
+        for j in 0..ny {
+            for i in 0..nx {
+                points[j * nx + i] = [
+                    (i as f64 + (j as f64 + self.offset()) / ny as f64) / nx as f64,
+                    (j as f64 + (i as f64 + self.offset()) / nx as f64) / ny as f64,
+                ];
+            }
+        }
+
+        // Shuffle the sub-columns in each column, and the sub-lines in each
+        // line
+        for i in 0..nx {
+            for j in 0..ny {
+                let k = self.rng.usize(j..ny);
+                let x = points[j * nx + i][0];
+                points[j * nx + i][0] = points[k * nx + i][0];
+                points[k * nx + i][0] = x;
+            }
+        }
+        for j in 0..ny {
+            for i in 0..nx {
+                let k = self.rng.usize(i..nx);
+                let y = points[j * nx + i][1];
+                points[j * nx + i][1] = points[j * nx + k][1];
+                points[j * nx + k][1] = y;
+            }
+        }
+
+        self.rng.shuffle(&mut points);
+        points
+    }
 }
 
 impl Sampler for StratifiedSampler {
     fn prepare(&mut self, nb_1d: usize, nb_2d: usize, nb_samples: usize) {
-        // TODO 1D
-        // TODO several 2d dimensions
-        // TODO shuffle between dimensions
-        assert_eq!(nb_2d, 1);
-
-        let root = (nb_samples as f64).sqrt() as usize;
-        assert_eq!(root * root, nb_samples);
-
-        let mut samples_2d = Vec::new();
-
-        for i in 0..root {
-            for j in 0..root {
-                // Not 0.5 to prevent rays from being parallel to the walls
-                // TODO 0.5
-                let (dx, dy) = if self.with_jittering {
-                    (self.rng.f64(), self.rng.f64())
-                } else {
-                    (0.501, 0.501)
-                };
-
-                samples_2d.push(vec![[
-                    (i as f64 + dx) / root as f64,
-                    (j as f64 + dy) / root as f64,
-                ]]);
-            }
-        }
+        self.nb_1d = nb_1d;
+        self.nb_2d = nb_2d;
+        self.nb_samples = nb_samples;
+    }
 
-        self.samples_2d = samples_2d;
-        self.current_sample = 0;
-        self.current_2d_dim = 0;
+    fn start_pixel(&mut self, x: usize, y: usize) {
+        self.pixel = (x, y);
+        self.rng = Rng::with_seed(mix_seed(&[self.seed, x as u64, y as u64]));
+
+        let nb_samples = self.nb_samples;
+        self.samples_1d = (0..self.nb_1d)
+            .map(|_| self.strata_1d(nb_samples))
+            .collect();
+        self.samples_2d = (0..self.nb_2d)
+            .map(|_| self.strata_2d(nb_samples))
+            .collect();
     }
 
-    fn new_sample(&mut self) {
-        if self.current_2d_dim != 0 {
-            // TODO and 1D dimension
-            self.current_sample += 1;
-            self.current_2d_dim = 0;
-        }
+    fn start_sample(&mut self, index: usize) {
+        // For the values beyond the prepared dimensions or samples
+        let (x, y) = self.pixel;
+        self.rng = Rng::with_seed(mix_seed(&[self.seed, x as u64, y as u64, index as u64]));
+
+        self.current_sample = index;
+        self.current_1d_dim = 0;
+        self.current_2d_dim = 0;
     }
 
     fn next1d(&mut self) -> f64 {
-        unimplemented!();
+        self.current_1d_dim += 1;
+
+        match self
+            .samples_1d
+            .get(self.current_1d_dim - 1)
+            .and_then(|samples| samples.get(self.current_sample))
+        {
+            Some(&value) => value,
+            None => self.rng.f64(),
+        }
     }
 
     fn next2d(&mut self) -> [f64; 2] {
         self.current_2d_dim += 1;
-        self.samples_2d[self.current_sample][self.current_2d_dim - 1]
+
+        match self
+            .samples_2d
+            .get(self.current_2d_dim - 1)
+            .and_then(|samples| samples.get(self.current_sample))
+        {
+            Some(&point) => point,
+            None => [self.rng.f64(), self.rng.f64()],
+        }
+    }
+
+    fn fingerprint(&self) -> u64 {
+        mix_seed(&[self.seed, self.with_jittering as u64])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strata() {
+        let mut sampler = StratifiedSampler::new(1);
+        sampler.prepare(2, 2, 12);
+        sampler.start_pixel(0, 0);
+
+        let mut strata_1d = vec![vec![0; 12]; 2];
+        let mut strata_2d = vec![vec![0; 12]; 2];
+        let mut columns = vec![vec![0; 12]; 2];
+        let mut lines = vec![vec![0; 12]; 2];
+
+        for index in 0..12 {
+            sampler.start_sample(index);
+            for dim in 0..2 {
+                let u = sampler.next1d();
+                let [x, y] = sampler.next2d();
+
+                strata_1d[dim][(12. * u) as usize] += 1;
+                // Grid of 3 columns and 4 lines
+                strata_2d[dim][(3. * x) as usize * 4 + (4. * y) as usize] += 1;
+                columns[dim][(12. * x) as usize] += 1;
+                lines[dim][(12. * y) as usize] += 1;
+            }
+
+            // Beyond the prepared dimensions
+            let u = sampler.next1d();
+            assert!((0. ..1.).contains(&u));
+        }
+
+        for counts in [strata_1d, strata_2d, columns, lines] {
+            assert!(counts.iter().flatten().all(|&count| count == 1));
+        }
     }
 }
@@ -1,19 +1,26 @@
-use crate::{Camera, Collision, Light, Primitive, Ray};
+use std::{collections::HashSet, sync::OnceLock};
+
+use crate::{BoundingBox, Bvh, Camera, Collision, Light, Primitive, Ray};
 
 #[derive(Clone, Debug)]
 pub struct Scene {
-    pub camera: Camera,
+    pub camera: Box<dyn Camera>,
     pub lights: Vec<Light>,
     primitives: Vec<Box<dyn Primitive>>,
+
+    // Hierarchy over the bounding boxes of the primitives, built when the
+    // first ray is cast
+    bvh: OnceLock<Bvh>,
 }
 
 impl Scene {
     /// Creates a new scene
-    pub const fn new(camera: Camera) -> Self {
+    pub const fn new(camera: Box<dyn Camera>) -> Self {
         Scene {
             camera,
             lights: Vec::new(),
             primitives: Vec::new(),
+            bvh: OnceLock::new(),
         }
     }
 
@@ -25,44 +32,55 @@ impl Scene {
     /// Adds an object to the scene
     pub fn add_primitive(&mut self, prim: Box<dyn Primitive>) {
         self.primitives.push(prim);
+        self.bvh = OnceLock::new();
+    }
+
+    fn bvh(&self) -> &Bvh {
+        self.bvh.get_or_init(|| {
+            let bounding_boxes: Vec<BoundingBox> =
+                self.primitives.iter().map(|p| p.bounding_box()).collect();
+            Bvh::new(&bounding_boxes)
+        })
+    }
+
+    /// Returns the memory used by the acceleration structures, in bytes,
+    /// counting the shared primitives and shapes once
+    pub fn acceleration_memory(&self) -> usize {
+        let mut counted = HashSet::new();
+        let primitives: usize = self
+            .primitives
+            .iter()
+            .map(|primitive| primitive.memory_size(&mut counted))
+            .sum();
+        self.bvh().memory_size() + primitives
     }
 
     /// Returns the time until a ray touches an object of the scene
     pub fn collision_date(&self, ray: Ray) -> f64 {
-        let mut earliest_collision = f64::INFINITY;
-
-        for prim in self.primitives.iter() {
-            if let Some(collision_date) = prim.collision_date(ray) {
-                if collision_date < earliest_collision {
-                    earliest_collision = collision_date;
-                }
-            }
+        match self
+            .bvh()
+            .first_collision(ray, |index| self.primitives[index].collision_date(ray))
+        {
+            None => f64::INFINITY,
+            Some((_, date)) => date,
         }
-
-        earliest_collision
     }
 
     /// Returns the object colliding with a ray and the information about the
     /// collision
-    pub fn collision(&self, ray: Ray) -> Option<(&Box<dyn Primitive>, Collision)> {
-        let mut earliest_collision: Option<(&Box<dyn Primitive>, f64)> = None;
-
-        for prim in self.primitives.iter() {
-            if let Some(collision_date) = prim.collision_date(ray) {
-                let earliest_date = match earliest_collision {
-                    None => f64::INFINITY,
-                    Some((_, date)) => date,
-                };
+    pub fn collision(&self, ray: Ray) -> Option<(&dyn Primitive, Collision)> {
+        self.indexed_collision(ray)
+            .map(|(_, primitive, collision)| (primitive, collision))
+    }
 
-                if collision_date < earliest_date {
-                    earliest_collision = Some((prim, collision_date));
-                }
-            }
-        }
+    /// Returns the index of the object colliding with a ray, in the order in
+    /// which the objects were added, with the object and the collision
+    pub fn indexed_collision(&self, ray: Ray) -> Option<(usize, &dyn Primitive, Collision)> {
+        let (index, _) = self
+            .bvh()
+            .first_collision(ray, |index| self.primitives[index].collision_date(ray))?;
 
-        match earliest_collision {
-            None => None,
-            Some((prim, _)) => Some((prim, prim.collision(ray).unwrap())),
-        }
+        let prim = self.primitives[index].as_ref();
+        Some((index, prim, prim.collision(ray).unwrap()))
     }
 }
@@ -0,0 +1,354 @@
+use crate::progress::{CancellationToken, Progress, ProgressCallback, ProgressTracker};
+use crate::stats::{self, RenderStats};
+use crate::{Color, Image};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    thread::{self, available_parallelism},
+    time::Instant,
+};
+
+/// Rectangle of pixels rendered by a single thread
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tile {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Tile {
+    /// Returns the pixels of the tile, line by line
+    pub fn pixels(&self) -> impl Iterator<Item = (usize, usize)> {
+        let (x, y, width) = (self.x, self.y, self.width);
+        (y..y + self.height).flat_map(move |py| (x..x + width).map(move |px| (px, py)))
+    }
+}
+
+/// Splits the images into tiles and renders them on several threads. Each
+/// thread starts with its own share of the tiles, and steals the tiles of
+/// the other threads once it is done, so that all the threads stay busy
+/// until the end.
+///
+/// The scheduler also carries the progress callback and the cancellation
+/// token of the renders: once the token is cancelled, the threads stop taking
+/// new tiles.
+pub struct Scheduler {
+    threads_count: usize,
+    tile_size: usize,
+    progress: Option<ProgressCallback>,
+    cancellation: CancellationToken,
+
+    // Statistics of the render being collected, if any. Each clone has its
+    // own, so that the renders of the clones do not mix their statistics.
+    stats: Mutex<Option<RenderStats>>,
+}
+
+impl Clone for Scheduler {
+    fn clone(&self) -> Self {
+        Self {
+            threads_count: self.threads_count,
+            tile_size: self.tile_size,
+            progress: self.progress.clone(),
+            cancellation: self.cancellation.clone(),
+            stats: Mutex::new(None),
+        }
+    }
+}
+
+impl Default for Scheduler {
+    /// Uses one thread per core, and tiles of 16x16 pixels
+    fn default() -> Self {
+        Self::new(available_parallelism().map_or(1, |n| n.get()))
+    }
+}
+
+impl Scheduler {
+    /// Creates a scheduler using a given number of threads
+    pub fn new(threads_count: usize) -> Self {
+        assert!(threads_count > 0);
+
+        Self {
+            threads_count,
+            tile_size: 16,
+            progress: None,
+            cancellation: CancellationToken::new(),
+            stats: Mutex::new(None),
+        }
+    }
+
+    /// Uses square tiles with a given side, in pixels
+    pub fn with_tile_size(mut self, tile_size: usize) -> Self {
+        assert!(tile_size > 0);
+
+        self.tile_size = tile_size;
+        self
+    }
+
+    /// Calls a function from the render threads each time some work is done
+    pub fn with_progress(mut self, callback: impl Fn(&Progress) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Arc::new(callback));
+        self
+    }
+
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = token;
+        self
+    }
+
+    pub fn threads_count(&self) -> usize {
+        self.threads_count
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
+    /// Runs a render, and returns its result with the statistics gathered
+    /// from the threads of the scheduler while it ran
+    pub fn collect_stats<R>(&self, render: impl FnOnce() -> R) -> (R, RenderStats) {
+        let start = Instant::now();
+        *self.stats.lock().unwrap() = Some(RenderStats::default());
+
+        let result = render();
+
+        let mut stats = self.stats.lock().unwrap().take().unwrap();
+        stats.elapsed = start.elapsed();
+        (result, stats)
+    }
+
+    /// Returns a tracker reporting to the progress callback, for a render
+    /// with a given amount of work
+    pub fn tracker(&self, total: u64) -> ProgressTracker {
+        ProgressTracker::new(self.progress.clone(), total)
+    }
+
+    /// Returns the tiles covering an image, in a spiral order starting from
+    /// the center of the image, where the subject usually is
+    pub fn tiles(&self, width: usize, height: usize) -> Vec<Tile> {
+        let size = self.tile_size;
+        let (columns, lines) = (width.div_ceil(size), height.div_ceil(size));
+
+        let mut tiles: Vec<Tile> = (0..lines)
+            .flat_map(|j| (0..columns).map(move |i| (i, j)))
+            .map(|(i, j)| Tile {
+                x: i * size,
+                y: j * size,
+                width: size.min(width - i * size),
+                height: size.min(height - j * size),
+            })
+            .collect();
+
+        // Sort by ring around the central tile, then by angle in the ring
+        let center = ((columns - 1) as f64 / 2., (lines - 1) as f64 / 2.);
+        let key = |tile: &Tile| {
+            let dx = (tile.x / size) as f64 - center.0;
+            let dy = (tile.y / size) as f64 - center.1;
+            (dx.abs().max(dy.abs()), dy.atan2(dx))
+        };
+        tiles.sort_by(|a, b| {
+            let (a, b) = (key(a), key(b));
+            a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1))
+        });
+
+        tiles
+    }
+
+    /// Renders tiles on the threads, and returns the results in the order of
+    /// the tiles, whatever the thread which rendered them. Each thread gets
+    /// its own state, created by init, for instance a sampler. The tiles which
+    /// are not rendered because the render is cancelled have no result.
+    pub fn run<State, T, I, F>(&self, tiles: &[Tile], init: I, render: F) -> Vec<Option<T>>
+    where
+        State: Send,
+        T: Send,
+        I: FnMut() -> State,
+        F: Fn(&mut State, Tile) -> T + Sync,
+    {
+        self.run_until(tiles, None, init, render)
+    }
+
+    /// Same as run, but the threads also stop taking new tiles once the
+    /// deadline has passed. The tiles being rendered are finished.
+    pub fn run_until<State, T, I, F>(
+        &self,
+        tiles: &[Tile],
+        deadline: Option<Instant>,
+        mut init: I,
+        render: F,
+    ) -> Vec<Option<T>>
+    where
+        State: Send,
+        T: Send,
+        I: FnMut() -> State,
+        F: Fn(&mut State, Tile) -> T + Sync,
+    {
+        let threads_count = self.threads_count.min(tiles.len()).max(1);
+
+        // The tiles are dealt in turn to the threads, so that the first
+        // tiles are rendered first
+        let queues: Vec<Mutex<VecDeque<usize>>> = (0..threads_count)
+            .map(|thread| Mutex::new((thread..tiles.len()).step_by(threads_count).collect()))
+            .collect();
+
+        let states: Vec<State> = (0..threads_count).map(|_| init()).collect();
+        let counting = self.progress.is_some() || self.stats.lock().unwrap().is_some();
+        let mut results: Vec<Option<T>> = (0..tiles.len()).map(|_| None).collect();
+
+        thread::scope(|scope| {
+            let handles: Vec<_> = states
+                .into_iter()
+                .enumerate()
+                .map(|(thread, mut state)| {
+                    let (queues, render) = (&queues, &render);
+                    let cancellation = &self.cancellation;
+
+                    let stats = &self.stats;
+
+                    scope.spawn(move || {
+                        stats::set_enabled(counting);
+                        let (start, counters) = (Instant::now(), stats::counters());
+
+                        let mut rendered = Vec::new();
+                        while let Some(index) = next_tile(queues, thread) {
+                            let late = deadline.is_some_and(|deadline| Instant::now() >= deadline);
+                            if cancellation.is_cancelled() || late {
+                                break;
+                            }
+                            rendered.push((index, render(&mut state, tiles[index])));
+                        }
+
+                        if let Some(stats) = stats.lock().unwrap().as_mut() {
+                            let counters = stats::counters() - counters;
+                            stats.add(thread, counters, start.elapsed());
+                        }
+                        rendered
+                    })
+                })
+                .collect();
+
+            for handle in handles {
+                for (index, result) in handle.join().unwrap() {
+                    results[index] = Some(result);
+                }
+            }
+        });
+
+        results
+    }
+
+    /// Renders an image whose pixels are computed independently of each
+    /// other. The pixels which are not rendered because the render is
+    /// cancelled are black.
+    pub fn render_image<F>(&self, width: usize, height: usize, color: F) -> Image
+    where
+        F: Fn(usize, usize) -> Color + Sync,
+    {
+        let tiles = self.tiles(width, height);
+        let tracker = self.tracker((width * height) as u64);
+
+        let colors = self.run(
+            &tiles,
+            || (),
+            |_, tile| {
+                let rays = stats::counters().rays();
+                let colors: Vec<Color> = tile.pixels().map(|(x, y)| color(x, y)).collect();
+
+                tracker.advance(colors.len() as u64, stats::counters().rays() - rays);
+                colors
+            },
+        );
+
+        let mut image = Image::new(width, height);
+        for (tile, colors) in tiles.iter().zip(colors) {
+            let Some(colors) = colors else { continue };
+
+            for ((x, y), color) in tile.pixels().zip(colors) {
+                image.set_pixel(x, y, color);
+            }
+        }
+
+        image
+    }
+}
+
+/// Returns the next tile of a thread, stolen from the end of the queue of
+/// another thread if its own queue is empty
+fn next_tile(queues: &[Mutex<VecDeque<usize>>], thread: usize) -> Option<usize> {
+    if let Some(index) = queues[thread].lock().unwrap().pop_front() {
+        return Some(index);
+    }
+
+    (1..queues.len())
+        .map(|offset| (thread + offset) % queues.len())
+        .find_map(|victim| queues[victim].lock().unwrap().pop_back())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scheduler() {
+        let scheduler = Scheduler::new(3).with_tile_size(4);
+        let tiles = scheduler.tiles(18, 9);
+        assert_eq!(tiles.len(), 5 * 3);
+
+        // The first tile is in the center, and each pixel is in exactly one
+        // tile
+        assert_eq!((tiles[0].x, tiles[0].y), (8, 4));
+        let mut counts = vec![0; 18 * 9];
+        for tile in &tiles {
+            for (x, y) in tile.pixels() {
+                counts[y * 18 + x] += 1;
+            }
+        }
+        assert!(counts.iter().all(|&count| count == 1));
+
+        // The results are in the order of the tiles, and each thread has its
+        // own state
+        let mut created = 0;
+        let results = scheduler.run(
+            &tiles,
+            || {
+                created += 1;
+                0
+            },
+            |rendered: &mut usize, tile| {
+                *rendered += 1;
+                tile.pixels().count()
+            },
+        );
+        assert_eq!(created, 3);
+        let expected: Vec<_> = tiles
+            .iter()
+            .map(|tile| Some(tile.width * tile.height))
+            .collect();
+        assert_eq!(results, expected);
+
+        // The progress reaches 1, and a cancelled render renders no tile
+        let fraction = Arc::new(Mutex::new(0.));
+        let last = Arc::clone(&fraction);
+        let token = CancellationToken::new();
+        let scheduler = scheduler
+            .with_progress(move |progress| {
+                let mut last = last.lock().unwrap();
+                *last = f64::max(*last, progress.fraction);
+            })
+            .with_cancellation(token.clone());
+
+        let image = scheduler.render_image(18, 9, |_, _| Color::white());
+        assert_eq!(image.pixel(17, 8).red, 1.);
+        assert_eq!(*fraction.lock().unwrap(), 1.);
+
+        token.cancel();
+        let results = scheduler.run(&tiles, || (), |_, _| ());
+        assert!(results.iter().all(Option::is_none));
+
+        // The statistics of a clone are not collected by the original
+        let clone = scheduler.clone();
+        let (_, stats) =
+            scheduler.collect_stats(|| clone.render_image(4, 4, |_, _| Color::white()));
+        assert!(stats.thread_times.is_empty());
+    }
+}
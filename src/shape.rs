@@ -1,4 +1,4 @@
-use std::fmt::Debug;
+use std::{collections::HashSet, fmt::Debug, sync::Arc};
 
 use crate::{BoundingBox, Ray, Vect};
 
@@ -12,6 +12,13 @@ pub trait Shape: Send + Sync + Debug + ShapeClone {
 
     // TODO no option
     fn collision(&self, ray: Ray) -> Option<Collision>;
+
+    /// Returns the memory used by the acceleration structures of the shape, in
+    /// bytes. The shared objects whose address is in counted are not counted
+    /// again.
+    fn memory_size(&self, _counted: &mut HashSet<*const ()>) -> usize {
+        0
+    }
 }
 
 pub trait ShapeClone {
@@ -30,6 +37,30 @@ impl Clone for Box<dyn Shape> {
     }
 }
 
+/// A shape can be shared between several primitives, for instance to use the
+/// same mesh with different materials without copying it
+impl Shape for Arc<dyn Shape> {
+    fn bounding_box(&self) -> BoundingBox {
+        self.as_ref().bounding_box()
+    }
+
+    fn collision_date(&self, ray: Ray) -> Option<f64> {
+        self.as_ref().collision_date(ray)
+    }
+
+    fn collision(&self, ray: Ray) -> Option<Collision> {
+        self.as_ref().collision(ray)
+    }
+
+    fn memory_size(&self, counted: &mut HashSet<*const ()>) -> usize {
+        if counted.insert(Arc::as_ptr(self).cast()) {
+            self.as_ref().memory_size(counted)
+        } else {
+            0
+        }
+    }
+}
+
 impl dyn Shape {
     /// Tests if the origin of the ray is inside the shape
     pub fn ray_starts_inside(&self, ray: Ray) -> bool {
@@ -53,4 +84,7 @@ pub struct Collision {
     /// an inside and an outside, the normal must point towards the outside.
     /// This is necessary to test whever a point is inside or outside the object.
     pub normal: Vect,
+
+    /// The texture coordinates of the point of collision, in [0; 1]
+    pub uv: (f64, f64),
 }
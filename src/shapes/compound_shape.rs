@@ -1,11 +1,16 @@
+use std::{collections::HashSet, sync::OnceLock};
+
 use crate::ray::Ray;
 use crate::shape::{Collision, Shape};
-use crate::BoundingBox;
+use crate::{BoundingBox, Bvh};
 
 #[derive(Clone, Debug)]
 pub struct CompoundShape {
     children: Vec<Box<dyn Shape>>,
     bounding_box: BoundingBox,
+
+    // Built when the first ray is cast, after all the children are added
+    bvh: OnceLock<Bvh>,
 }
 
 impl CompoundShape {
@@ -13,12 +18,29 @@ impl CompoundShape {
         Box::new(Self {
             children: Vec::new(),
             bounding_box: BoundingBox::new(),
+            bvh: OnceLock::new(),
         })
     }
 
     pub fn add(&mut self, shape: Box<dyn Shape>) {
         self.bounding_box = &self.bounding_box + &shape.bounding_box();
         self.children.push(shape);
+        self.bvh = OnceLock::new();
+    }
+
+    fn bvh(&self) -> &Bvh {
+        self.bvh.get_or_init(|| {
+            let bounding_boxes: Vec<BoundingBox> =
+                self.children.iter().map(|c| c.bounding_box()).collect();
+            Bvh::new(&bounding_boxes)
+        })
+    }
+
+    /// Returns the index of the first child hit by a ray, and the date of
+    /// the collision
+    fn first_collision(&self, ray: Ray) -> Option<(usize, f64)> {
+        self.bvh()
+            .first_collision(ray, |index| self.children[index].collision_date(ray))
     }
 }
 
@@ -28,41 +50,20 @@ impl Shape for CompoundShape {
     }
 
     fn collision_date(&self, ray: Ray) -> Option<f64> {
-        if self.bounding_box.collision_date(ray) == None {
-            return None;
-        }
-
-        let mut current_collision = None;
-
-        for child in &self.children {
-            current_collision = match (current_collision, child.collision_date(ray)) {
-                (None, col) => col,
-                (Some(t1), Some(t2)) if t2 < t1 => Some(t2),
-                (col, _) => col,
-            };
-        }
-
-        current_collision
+        self.first_collision(ray).map(|(_, date)| date)
     }
 
-    // TODO there should be a way to store information to avoid recomputing everything
     fn collision(&self, ray: Ray) -> Option<Collision> {
-        if self.bounding_box.collision_date(ray) == None {
-            return None;
-        }
-
-        if let Some(date) = self.collision_date(ray) {
-            for child in &self.children {
-                if let Some(date_child) = child.collision_date(ray) {
-                    if date == date_child {
-                        return child.collision(ray);
-                    }
-                }
-            }
+        let (index, _) = self.first_collision(ray)?;
+        self.children[index].collision(ray)
+    }
 
-            unreachable!()
-        } else {
-            None
-        }
+    fn memory_size(&self, counted: &mut HashSet<*const ()>) -> usize {
+        let children: usize = self
+            .children
+            .iter()
+            .map(|child| child.memory_size(counted))
+            .sum();
+        self.bvh().memory_size() + children
     }
 }
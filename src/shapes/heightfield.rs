@@ -0,0 +1,339 @@
+use crate::shape::{Collision, Shape};
+use crate::{BoundingBox, Ray, Vect};
+
+/// Terrain whose altitude is given by a grid of samples. The samples are
+/// spread regularly over the rectangle [0; size.x] x [0; size.y], and a sample
+/// of value h (in [0; 1]) has an altitude of h * size.z. The surface is
+/// interpolated bilinearly between the samples.
+///
+/// The texture coordinates follow the lines of the heightmap loaded by
+/// from_image: v is 0 on its first line, at y = size.y, and 1 at y = 0.
+#[derive(Clone, Debug)]
+pub struct Heightfield {
+    // Number of samples along each axis
+    nx: usize,
+    ny: usize,
+
+    // Altitudes of the samples, where the sample (i, j) is at index j * nx + i
+    heights: Vec<f64>,
+
+    // Smooth normals at each sample, with the same layout as the altitudes
+    normals: Vec<Vect>,
+
+    // Size of a cell
+    cell_x: f64,
+    cell_y: f64,
+
+    size: Vect,
+    bounding_box: BoundingBox,
+}
+
+impl Heightfield {
+    /// Creates a heightfield from the values of the samples. As for images,
+    /// heights[x][y] is the value of the sample on the x-th column and the
+    /// y-th line, starting from the bottom.
+    pub fn new(heights: Vec<Vec<f64>>, size: Vect) -> Box<Self> {
+        let nx = heights.len();
+        assert!(nx >= 2);
+        let ny = heights[0].len();
+        assert!(ny >= 2);
+
+        let mut altitudes = vec![0.; nx * ny];
+        for (i, column) in heights.iter().enumerate() {
+            assert_eq!(column.len(), ny);
+            for (j, h) in column.iter().enumerate() {
+                altitudes[j * nx + i] = h * size.z;
+            }
+        }
+
+        let cell_x = size.x / (nx - 1) as f64;
+        let cell_y = size.y / (ny - 1) as f64;
+
+        // Normals are computed from the central differences of the altitudes
+        let altitude = |i: usize, j: usize| altitudes[j * nx + i];
+        let mut normals = Vec::with_capacity(nx * ny);
+        for j in 0..ny {
+            for i in 0..nx {
+                let (i0, i1) = (i.saturating_sub(1), (i + 1).min(nx - 1));
+                let (j0, j1) = (j.saturating_sub(1), (j + 1).min(ny - 1));
+
+                let dhdx = (altitude(i1, j) - altitude(i0, j)) / ((i1 - i0) as f64 * cell_x);
+                let dhdy = (altitude(i, j1) - altitude(i, j0)) / ((j1 - j0) as f64 * cell_y);
+
+                normals.push(Vect::new(-dhdx, -dhdy, 1.).normalized());
+            }
+        }
+
+        let min = altitudes.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = altitudes.iter().cloned().fold(-f64::INFINITY, f64::max);
+        let bounding_box = BoundingBox::new_from_extremities(
+            Vect::new(0., 0., min),
+            Vect::new(size.x, size.y, max),
+        );
+
+        Box::new(Self {
+            nx,
+            ny,
+            heights: altitudes,
+            normals,
+            cell_x,
+            cell_y,
+            size,
+            bounding_box,
+        })
+    }
+
+    /// Loads a heightfield from a greyscale image. Each pixel is a sample:
+    /// black pixels have an altitude of 0, and white pixels of size.z.
+    pub fn from_image(filename: &str, size: Vect) -> Box<Self> {
+        let image = image::open(filename)
+            .expect("Could not open the heightmap")
+            .into_luma16();
+
+        let (width, height) = (image.width() as usize, image.height() as usize);
+
+        // The first line of the image is the top of the terrain
+        let heights = (0..width)
+            .map(|x| {
+                (0..height)
+                    .map(|y| {
+                        let pixel = image.get_pixel(x as u32, (height - y - 1) as u32);
+                        pixel.0[0] as f64 / u16::MAX as f64
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Self::new(heights, size)
+    }
+
+    fn altitude(&self, i: usize, j: usize) -> f64 {
+        self.heights[j * self.nx + i]
+    }
+
+    /// Returns the date of the first collision in the cell (i, j), between
+    /// the dates t_min and t_max
+    fn cell_collision_date(
+        &self,
+        ray: Ray,
+        i: usize,
+        j: usize,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<f64> {
+        let h00 = self.altitude(i, j);
+        let h10 = self.altitude(i + 1, j);
+        let h01 = self.altitude(i, j + 1);
+        let h11 = self.altitude(i + 1, j + 1);
+
+        // Skip the cell if the ray stays above or below it
+        let z_min = ray.pos_in(t_min).z.min(ray.pos_in(t_max).z);
+        let z_max = ray.pos_in(t_min).z.max(ray.pos_in(t_max).z);
+        if z_min > h00.max(h10).max(h01).max(h11) || z_max < h00.min(h10).min(h01).min(h11) {
+            return None;
+        }
+
+        // Local coordinates in the cell along the ray: u = cu + au * t and
+        // v = cv + av * t
+        let cu = ray.pos.x / self.cell_x - i as f64;
+        let au = ray.dir.x / self.cell_x;
+        let cv = ray.pos.y / self.cell_y - j as f64;
+        let av = ray.dir.y / self.cell_y;
+
+        // The altitude of the surface is h00 + e * u + f * v + g * u * v, so
+        // the ray hits it when a * t^2 + b * t + c = 0
+        let e = h10 - h00;
+        let f = h01 - h00;
+        let g = h00 - h10 - h01 + h11;
+
+        let a = -g * au * av;
+        let b = ray.dir.z - (e * au + f * av + g * (cu * av + cv * au));
+        let c = ray.pos.z - (h00 + e * cu + f * cv + g * cu * cv);
+
+        // Tolerance for the points lying on the border of the cell
+        let tolerance = 1e-9 * (1. + t_max.abs());
+        let is_valid = |t: f64| t > 0. && t >= t_min - tolerance && t <= t_max + tolerance;
+
+        if a.abs() <= 1e-12 * (b.abs() + c.abs()) {
+            if b == 0. {
+                return None;
+            }
+            let t = -c / b;
+            return if is_valid(t) { Some(t) } else { None };
+        }
+
+        let delta = b * b - 4. * a * c;
+        if delta < 0. {
+            return None;
+        }
+
+        // Numerically stable roots
+        let q = -0.5 * (b + b.signum() * delta.sqrt());
+        let (mut t1, mut t2) = (q / a, c / q);
+        if t1 > t2 {
+            std::mem::swap(&mut t1, &mut t2);
+        }
+
+        if is_valid(t1) {
+            Some(t1)
+        } else if is_valid(t2) {
+            Some(t2)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the date of the first collision and the cell where it occurs,
+    /// by traversing the cells crossed by the ray
+    fn find_collision(&self, ray: Ray) -> Option<(f64, usize, usize)> {
+        let (t_enter, t_exit) = self.bounding_box.collision_interval(ray)?;
+
+        let start = ray.pos_in(t_enter);
+        let mut i = ((start.x / self.cell_x).floor().max(0.) as usize).min(self.nx - 2);
+        let mut j = ((start.y / self.cell_y).floor().max(0.) as usize).min(self.ny - 2);
+
+        // Date at which the ray crosses the next line of the grid, and
+        // duration between two crossings
+        let next_crossing = |pos: f64, dir: f64, index: usize, cell: f64| {
+            if dir > 0. {
+                ((index + 1) as f64 * cell - pos) / dir
+            } else if dir < 0. {
+                (index as f64 * cell - pos) / dir
+            } else {
+                f64::INFINITY
+            }
+        };
+        let mut t_next_x = next_crossing(ray.pos.x, ray.dir.x, i, self.cell_x);
+        let mut t_next_y = next_crossing(ray.pos.y, ray.dir.y, j, self.cell_y);
+        let t_delta_x = self.cell_x / ray.dir.x.abs();
+        let t_delta_y = self.cell_y / ray.dir.y.abs();
+
+        let mut t = t_enter;
+
+        loop {
+            let t_cell_exit = t_next_x.min(t_next_y).min(t_exit);
+
+            if let Some(date) = self.cell_collision_date(ray, i, j, t, t_cell_exit) {
+                return Some((date, i, j));
+            }
+
+            if t_cell_exit >= t_exit {
+                return None;
+            }
+
+            if t_next_x < t_next_y {
+                if ray.dir.x > 0. {
+                    i += 1;
+                    if i >= self.nx - 1 {
+                        return None;
+                    }
+                } else {
+                    if i == 0 {
+                        return None;
+                    }
+                    i -= 1;
+                }
+                t_next_x += t_delta_x;
+            } else {
+                if ray.dir.y > 0. {
+                    j += 1;
+                    if j >= self.ny - 1 {
+                        return None;
+                    }
+                } else {
+                    if j == 0 {
+                        return None;
+                    }
+                    j -= 1;
+                }
+                t_next_y += t_delta_y;
+            }
+
+            t = t_cell_exit;
+        }
+    }
+}
+
+impl Shape for Heightfield {
+    fn bounding_box(&self) -> BoundingBox {
+        self.bounding_box.clone()
+    }
+
+    fn collision_date(&self, ray: Ray) -> Option<f64> {
+        self.find_collision(ray).map(|(date, _, _)| date)
+    }
+
+    fn collision(&self, ray: Ray) -> Option<Collision> {
+        let (date, i, j) = self.find_collision(ray)?;
+        let pos = ray.pos_in(date);
+
+        // Interpolate the normals of the corners of the cell
+        let u = (pos.x / self.cell_x - i as f64).clamp(0., 1.);
+        let v = (pos.y / self.cell_y - j as f64).clamp(0., 1.);
+        let n = |i: usize, j: usize| self.normals[j * self.nx + i];
+        let normal = ((1. - u) * (1. - v)) * n(i, j)
+            + (u * (1. - v)) * n(i + 1, j)
+            + ((1. - u) * v) * n(i, j + 1)
+            + (u * v) * n(i + 1, j + 1);
+
+        Some(Collision {
+            date,
+            pos,
+            normal: normal.normalized(),
+            uv: (
+                (pos.x / self.size.x).clamp(0., 1.),
+                (1. - pos.y / self.size.y).clamp(0., 1.),
+            ),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heightfield() {
+        // Ramp going up along the x axis, with a bump in the middle
+        let heights = (0..5)
+            .map(|i| {
+                (0..3)
+                    .map(|j| if i == 2 && j == 1 { 1. } else { i as f64 / 8. })
+                    .collect()
+            })
+            .collect();
+        let heightfield = Heightfield::new(heights, Vect::new(4., 2., 2.));
+
+        // Vertical ray
+        let ray = Ray::new(Vect::new(0.5, 0.5, 10.), Vect::new(0., 0., -1.));
+        let col = heightfield.collision(ray).unwrap();
+        assert!((col.pos.z - 0.125).abs() <= 1e-6);
+        assert!((col.uv.0 - 0.125).abs() <= 1e-6 && (col.uv.1 - 0.75).abs() <= 1e-6);
+        assert!(col.normal.z > 0. && col.normal.x < 0.);
+
+        // Horizontal ray hitting the bump
+        let ray = Ray::new(Vect::new(-1., 1., 1.5), Vect::new(1., 0., 0.));
+        let col = heightfield.collision(ray).unwrap();
+        assert!(col.pos.x > 1. && col.pos.x < 2.);
+        assert!((col.pos.z - 1.5).abs() <= 1e-6);
+
+        // Ray passing above the terrain
+        let ray = Ray::new(Vect::new(-1., 0.2, 2.1), Vect::new(1., 0.1, -0.01));
+        assert!(heightfield.collision_date(ray).is_none());
+
+        // A heightmap whose first line is white: v is 0 there, as on the
+        // image
+        let path = std::env::temp_dir().join("renderer-test-heightmap.png");
+        let image = image::ImageBuffer::from_fn(5, 3, |_, y| {
+            image::Luma([if y == 0 { u16::MAX } else { 0 }])
+        });
+        image.save(&path).unwrap();
+        let heightfield = Heightfield::from_image(path.to_str().unwrap(), Vect::new(4., 2., 2.));
+        std::fs::remove_file(&path).unwrap();
+
+        let ray = Ray::new(Vect::new(2., 1.9, 10.), Vect::new(0., 0., -1.));
+        let col = heightfield.collision(ray).unwrap();
+        assert!((col.pos.z - 1.8).abs() <= 1e-6);
+        assert!((col.uv.1 - 0.05).abs() <= 1e-6);
+    }
+}
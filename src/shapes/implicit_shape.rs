@@ -11,29 +11,112 @@ pub trait ImplicitShape: Send + Sync + Debug {
     /// the object.
     fn estimated_distance(&self, point: Vect) -> f64;
 
-    /// Returns the gradient of the estimated distance at a given point.
-    fn grad(&self, point: Vect) -> Vect;
+    /// Returns the gradient of the estimated distance at a given point. By
+    /// default, it is approximated using central differences.
+    fn grad(&self, point: Vect) -> Vect {
+        let h = 1e-6;
+        let dx = Vect::new(h, 0., 0.);
+        let dy = Vect::new(0., h, 0.);
+        let dz = Vect::new(0., 0., h);
+
+        (0.5 / h)
+            * Vect::new(
+                self.estimated_distance(point + dx) - self.estimated_distance(point - dx),
+                self.estimated_distance(point + dy) - self.estimated_distance(point - dy),
+                self.estimated_distance(point + dz) - self.estimated_distance(point - dz),
+            )
+    }
+
+    /// Returns a finite box containing the object. Sphere tracing starts at
+    /// the point where the ray enters the box and stops when it leaves it.
+    fn bounds(&self) -> BoundingBox;
+
+    /// Returns the parameters used to find the collisions with the object
+    fn sphere_tracing(&self) -> SphereTracing {
+        SphereTracing::default()
+    }
+}
+
+/// Parameters of the sphere tracing algorithm
+#[derive(Copy, Clone, Debug)]
+pub struct SphereTracing {
+    /// Maximal distance travelled by a ray before considering that it misses
+    /// the object
+    pub max_distance: f64,
+
+    /// A point is considered to be on the surface if its estimated distance
+    /// is lower than epsilon times the distance travelled by the ray (or than
+    /// epsilon if the ray travelled less than one unit)
+    pub epsilon: f64,
+
+    /// Maximal number of evaluations of the distance along a ray
+    pub max_steps: usize,
+
+    /// Over-relaxation factor, between 1 and 2. The steps are multiplied by
+    /// this factor as long as the unbounding spheres overlap.
+    pub relaxation: f64,
+}
+
+impl Default for SphereTracing {
+    fn default() -> Self {
+        Self {
+            max_distance: f64::INFINITY,
+            epsilon: 1e-7,
+            max_steps: 1000,
+            relaxation: 1.6,
+        }
+    }
 }
 
 impl<T: ImplicitShape + Clone + 'static> Shape for T {
     fn bounding_box(&self) -> BoundingBox {
-        // TODO use a smaaller box
-        BoundingBox::new_full()
+        self.bounds()
     }
 
     fn collision_date(&self, ray: Ray) -> Option<f64> {
-        let ray_norm_inv = 1. / ray.dir.norm();
+        let params = self.sphere_tracing();
+        let (t_enter, t_exit) = self.bounds().collision_interval(ray)?;
 
-        let mut t = 0.;
+        // Dates are expressed in ray units, distances in world units
+        let ray_norm = ray.dir.norm();
+        let t_max = t_exit.min(params.max_distance / ray_norm);
 
-        while t < 100. {
-            let dist = self.estimated_distance(ray.pos_in(t)).abs();
+        let mut t = t_enter;
+        let mut omega = params.relaxation;
+        let mut previous_distance: f64 = 0.;
+        let mut step = 0.;
 
-            if dist < 1e-8 {
+        for _ in 0..params.max_steps {
+            let overshoot = t > t_max;
+            if overshoot && omega == 1. {
+                return None;
+            }
+
+            let distance = self.estimated_distance(ray.pos_in(t));
+            let radius = distance.abs();
+
+            // With over-relaxation, the current step is only valid if the
+            // unbounding spheres of the two last points overlap and if the
+            // surface was not crossed. Otherwise, go back to the previous
+            // point and use plain sphere tracing.
+            if omega > 1.
+                && (overshoot
+                    || radius + previous_distance.abs() < step
+                    || distance * previous_distance < 0.)
+            {
+                t -= step / ray_norm;
+                omega = 1.;
+                step = 0.;
+                continue;
+            }
+
+            if radius < params.epsilon * (t * ray_norm).max(1.) {
                 return Some(t);
             }
 
-            t += dist * ray_norm_inv;
+            previous_distance = distance;
+            step = omega * radius;
+            t += step / ray_norm;
         }
 
         None
@@ -47,9 +130,49 @@ impl<T: ImplicitShape + Clone + 'static> Shape for T {
                 date,
                 pos,
                 normal: self.grad(pos).normalized(),
+                uv: (0., 0.),
             })
         } else {
             None
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shapes::implicit_shapes::Cube;
+
+    #[test]
+    fn test_far_collision() {
+        let cube = Cube::new();
+
+        let ray = Ray::new(Vect::new(-1000., 0.2, 0.3), Vect::new(1., 0., 0.));
+        let date = cube.collision_date(ray).unwrap();
+        assert!((date - 999.).abs() <= 1e-3);
+
+        // Not normalized direction
+        let ray = Ray {
+            pos: Vect::new(0.5, 0.5, 500.),
+            dir: Vect::new(0., 0., -4.),
+            time: 0.,
+        };
+        let date = cube.collision_date(ray).unwrap();
+        assert!((date - 124.75).abs() <= 1e-3);
+    }
+
+    #[test]
+    fn test_grazing_and_inside_rays() {
+        let cube = Cube::new();
+
+        // Ray passing just next to an edge
+        let ray = Ray::new(Vect::new(-10., 1. + 1e-4, 0.), Vect::new(1., 0., 0.));
+        assert!(cube.collision_date(ray).is_none());
+
+        // Ray starting inside the cube
+        let ray = Ray::new(Vect::new(0., 0., 0.5), Vect::new(0., 0., 1.));
+        let col = cube.collision(ray).unwrap();
+        assert!((col.date - 0.5).abs() <= 1e-3);
+        assert!(col.normal * ray.dir > 0.);
+    }
+}
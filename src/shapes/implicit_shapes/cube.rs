@@ -1,7 +1,24 @@
-use crate::{shapes::implicit_shape::ImplicitShape, vect::Vect};
+use crate::{
+    shapes::implicit_shape::{ImplicitShape, SphereTracing},
+    vect::Vect,
+    BoundingBox,
+};
 
+/// Cube of side 2 centered on the origin
 #[derive(Clone, Debug)]
-pub struct Cube {}
+pub struct Cube {
+    sphere_tracing: SphereTracing,
+}
+
+impl Cube {
+    pub fn new() -> Box<Self> {
+        Self::new_with_sphere_tracing(SphereTracing::default())
+    }
+
+    pub fn new_with_sphere_tracing(sphere_tracing: SphereTracing) -> Box<Self> {
+        Box::new(Self { sphere_tracing })
+    }
+}
 
 impl ImplicitShape for Cube {
     fn estimated_distance(&self, point: Vect) -> f64 {
@@ -22,4 +39,12 @@ impl ImplicitShape for Cube {
             Vect::new(0., 0., point.z.signum())
         }
     }
+
+    fn bounds(&self) -> BoundingBox {
+        BoundingBox::new_from_extremities(Vect::new(-1., -1., -1.), Vect::new(1., 1., 1.))
+    }
+
+    fn sphere_tracing(&self) -> SphereTracing {
+        self.sphere_tracing
+    }
 }
@@ -0,0 +1,10 @@
+use crate::{shapes::ImplicitShape, Vect};
+
+/// A fractal is an implicit shape whose distance is estimated by iterating a
+/// function. The orbit of a point under this function can be used to colour
+/// the surface.
+pub trait Fractal: ImplicitShape {
+    /// Returns the orbit trap of a point, a value in [0; 1] summarizing the
+    /// orbit of the point.
+    fn orbit_trap(&self, point: Vect) -> f64;
+}
@@ -0,0 +1,110 @@
+use crate::{
+    shapes::{implicit_shapes::Fractal, ImplicitShape, SphereTracing},
+    BoundingBox, Vect,
+};
+
+/// Mandelbulb centered on the origin, obtained by iterating z -> z^power + c
+/// in spherical coordinates
+#[derive(Clone, Debug)]
+pub struct Mandelbulb {
+    power: f64,
+    iterations_count: usize,
+    sphere_tracing: SphereTracing,
+}
+
+impl Mandelbulb {
+    pub fn new(power: f64, iterations_count: usize) -> Box<Self> {
+        Self::new_with_sphere_tracing(power, iterations_count, SphereTracing::default())
+    }
+
+    pub fn new_with_sphere_tracing(
+        power: f64,
+        iterations_count: usize,
+        sphere_tracing: SphereTracing,
+    ) -> Box<Self> {
+        assert!(power > 1.);
+
+        Box::new(Self {
+            power,
+            iterations_count,
+            sphere_tracing,
+        })
+    }
+
+    /// Iterates the function from a point. Returns the estimated distance and
+    /// the orbit trap.
+    fn iterate(&self, point: Vect) -> (f64, f64) {
+        let mut z = point;
+        let mut dr = 1.;
+        let mut r = z.norm();
+        let mut trap = r;
+
+        for _ in 0..self.iterations_count {
+            if r > 2. || r == 0. {
+                break;
+            }
+
+            let theta = (z.z / r).acos() * self.power;
+            let phi = z.y.atan2(z.x) * self.power;
+            let zr = r.powf(self.power);
+            dr = self.power * r.powf(self.power - 1.) * dr + 1.;
+
+            z =
+                zr * Vect::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                ) + point;
+            r = z.norm();
+            trap = trap.min(r);
+        }
+
+        let distance = if r == 0. { 0. } else { 0.5 * r.ln() * r / dr };
+
+        (distance, trap.min(1.))
+    }
+}
+
+impl ImplicitShape for Mandelbulb {
+    fn estimated_distance(&self, point: Vect) -> f64 {
+        self.iterate(point).0
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        // Points farther than 2 from the origin always escape
+        BoundingBox::new_from_extremities(Vect::new(-2., -2., -2.), Vect::new(2., 2., 2.))
+    }
+
+    fn sphere_tracing(&self) -> SphereTracing {
+        self.sphere_tracing
+    }
+}
+
+impl Fractal for Mandelbulb {
+    fn orbit_trap(&self, point: Vect) -> f64 {
+        self.iterate(point).1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Ray, Shape};
+
+    #[test]
+    fn test_mandelbulb() {
+        let mandelbulb = Mandelbulb::new(8., 10);
+
+        // The origin is inside and the points far away are outside
+        assert!(mandelbulb.estimated_distance(Vect::new(0.1, 0., 0.)) <= 0.);
+        assert!(mandelbulb.estimated_distance(Vect::new(1.9, 1.9, 0.)) > 0.);
+
+        let ray = Ray::new(Vect::new(0., 0., 10.), Vect::new(0., 0., -1.));
+        let col = mandelbulb.collision(ray).unwrap();
+        assert!(col.pos.z > 0.5 && col.pos.z < 2.);
+        assert!(col.normal.z > 0.);
+
+        let trap = mandelbulb.orbit_trap(col.pos);
+        assert!((0. ..=1.).contains(&trap));
+    }
+}
@@ -0,0 +1,113 @@
+use crate::{
+    shapes::{implicit_shapes::Fractal, ImplicitShape, SphereTracing},
+    BoundingBox, Vect,
+};
+
+/// Menger sponge of side 2 centered on the origin
+#[derive(Clone, Debug)]
+pub struct MengerSponge {
+    iterations_count: usize,
+    sphere_tracing: SphereTracing,
+}
+
+impl MengerSponge {
+    pub fn new(iterations_count: usize) -> Box<Self> {
+        Self::new_with_sphere_tracing(iterations_count, SphereTracing::default())
+    }
+
+    pub fn new_with_sphere_tracing(
+        iterations_count: usize,
+        sphere_tracing: SphereTracing,
+    ) -> Box<Self> {
+        Box::new(Self {
+            iterations_count,
+            sphere_tracing,
+        })
+    }
+
+    /// Carves the cube iteratively. Returns the estimated distance and the
+    /// orbit trap.
+    fn iterate(&self, point: Vect) -> (f64, f64) {
+        // Distance to the cube
+        let q = Vect::new(point.x.abs() - 1., point.y.abs() - 1., point.z.abs() - 1.);
+        let outside = Vect::new(q.x.max(0.), q.y.max(0.), q.z.max(0.));
+        let mut distance = outside.norm() + q.x.max(q.y).max(q.z).min(0.);
+
+        let mut trap: f64 = 1.;
+        let mut scale = 1.;
+
+        for _ in 0..self.iterations_count {
+            // Position in the current sub-cube, in [-1; 1]
+            let a = Vect::new(
+                (scale * point.x).rem_euclid(2.) - 1.,
+                (scale * point.y).rem_euclid(2.) - 1.,
+                (scale * point.z).rem_euclid(2.) - 1.,
+            );
+            scale *= 3.;
+
+            let r = Vect::new(
+                (1. - 3. * a.x.abs()).abs(),
+                (1. - 3. * a.y.abs()).abs(),
+                (1. - 3. * a.z.abs()).abs(),
+            );
+            let da = r.x.max(r.y);
+            let db = r.y.max(r.z);
+            let dc = r.z.max(r.x);
+
+            // Distance to the cross removed at this iteration
+            let c = (da.min(db).min(dc) - 1.) / scale;
+
+            if c > distance {
+                distance = c;
+                trap = trap.min(0.2 * da * db * dc);
+            }
+        }
+
+        (distance, trap.clamp(0., 1.))
+    }
+}
+
+impl ImplicitShape for MengerSponge {
+    fn estimated_distance(&self, point: Vect) -> f64 {
+        self.iterate(point).0
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        BoundingBox::new_from_extremities(Vect::new(-1., -1., -1.), Vect::new(1., 1., 1.))
+    }
+
+    fn sphere_tracing(&self) -> SphereTracing {
+        self.sphere_tracing
+    }
+}
+
+impl Fractal for MengerSponge {
+    fn orbit_trap(&self, point: Vect) -> f64 {
+        self.iterate(point).1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Ray, Shape};
+
+    #[test]
+    fn test_menger_sponge() {
+        let sponge = MengerSponge::new(4);
+
+        // The center of the sponge is empty
+        let ray = Ray::new(Vect::new(-5., 0., 0.), Vect::new(1., 0., 0.));
+        assert!(sponge.collision_date(ray).is_none());
+
+        // The corners are full
+        let ray = Ray::new(Vect::new(-5., 0.95, 0.95), Vect::new(1., 0., 0.));
+        let col = sponge.collision(ray).unwrap();
+        assert!((col.pos.x + 1.).abs() <= 1e-4);
+        assert!(col.normal.x < -0.9);
+
+        // Holes of the second iteration
+        let ray = Ray::new(Vect::new(-5., 2. / 3., 2. / 3.), Vect::new(1., 0., 0.));
+        assert!(sponge.collision_date(ray).is_none());
+    }
+}
@@ -1,3 +1,9 @@
 pub mod cube;
+pub mod fractal;
+pub mod mandelbulb;
+pub mod menger_sponge;
 
 pub use cube::Cube;
+pub use fractal::Fractal;
+pub use mandelbulb::Mandelbulb;
+pub use menger_sponge::MengerSponge;
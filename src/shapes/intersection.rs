@@ -1,3 +1,5 @@
+use std::collections::HashSet;
+
 use crate::{BoundingBox, Collision, Ray, Shape};
 
 /// Intersection of two shapes
@@ -20,11 +22,11 @@ impl Shape for Intersection {
     }
 
     fn collision_date(&self, ray: Ray) -> Option<f64> {
-        if let Some(col) = self.collision(ray) {
-            Some(col.date)
-        } else {
-            None
-        }
+        self.collision(ray).map(|col| col.date)
+    }
+
+    fn memory_size(&self, counted: &mut HashSet<*const ()>) -> usize {
+        self.shapes[0].memory_size(counted) + self.shapes[1].memory_size(counted)
     }
 
     fn collision(&self, mut ray: Ray) -> Option<Collision> {
@@ -68,6 +70,7 @@ impl Shape for Intersection {
                     date: col.date + time_spent,
                     pos: col.pos,
                     normal: col.normal,
+                    uv: col.uv,
                 });
             }
 
@@ -1,4 +1,5 @@
 pub mod compound_shape;
+pub mod heightfield;
 pub mod implicit_shape;
 pub mod implicit_shapes;
 pub mod intersection;
@@ -6,7 +7,8 @@ pub mod sphere;
 pub mod triangle;
 
 pub use compound_shape::CompoundShape;
-pub use implicit_shape::ImplicitShape;
+pub use heightfield::Heightfield;
+pub use implicit_shape::{ImplicitShape, SphereTracing};
 pub use intersection::Intersection;
 pub use sphere::Sphere;
 pub use triangle::Triangle;
@@ -1,3 +1,5 @@
+use std::f64::consts::PI;
+
 use crate::shape::{Collision, Shape};
 use crate::{BoundingBox, Ray, Vect};
 
@@ -39,7 +41,7 @@ impl Shape for Sphere {
             return Some(root2);
         }
 
-        return None;
+        None
     }
 
     fn collision(&self, ray: Ray) -> Option<Collision> {
@@ -48,10 +50,15 @@ impl Shape for Sphere {
 
             let normal = (pos - self.center).normalized();
 
+            // Spherical coordinates
+            let u = 0.5 + normal.y.atan2(normal.x) / (2. * PI);
+            let v = normal.z.clamp(-1., 1.).acos() / PI;
+
             Some(Collision {
                 date: collision_date,
                 pos,
                 normal,
+                uv: (u, v),
             })
         } else {
             None
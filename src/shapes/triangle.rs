@@ -14,6 +14,70 @@ impl Triangle {
     pub fn new(a: Vect, b: Vect, c: Vect) -> Box<Self> {
         Box::new(Self { a, b, c })
     }
+
+    /// Returns the date of the collision with a ray, and the barycentric
+    /// coordinates of the point of collision (the weights of a, b and c).
+    ///
+    /// The test is watertight (Woop, Benthin and Wald, 2013): a ray going
+    /// through an edge or a vertex shared by several triangles hits at least
+    /// one of them. Degenerate triangles are never hit.
+    pub fn intersect(&self, ray: Ray) -> Option<(f64, [f64; 3])> {
+        let dir = ray.dir;
+
+        // Permute the axes so that the ray mostly goes along the z axis
+        let kz = if dir.x.abs() >= dir.y.abs() && dir.x.abs() >= dir.z.abs() {
+            0
+        } else if dir.y.abs() >= dir.z.abs() {
+            1
+        } else {
+            2
+        };
+        let mut kx = (kz + 1) % 3;
+        let mut ky = (kx + 1) % 3;
+        // Preserve the winding of the triangle
+        if dir.component(kz) < 0. {
+            std::mem::swap(&mut kx, &mut ky);
+        }
+
+        // Shear transformation making the ray go along the z axis
+        let sx = dir.component(kx) / dir.component(kz);
+        let sy = dir.component(ky) / dir.component(kz);
+        let sz = 1. / dir.component(kz);
+
+        let a = self.a - ray.pos;
+        let b = self.b - ray.pos;
+        let c = self.c - ray.pos;
+
+        let ax = a.component(kx) - sx * a.component(kz);
+        let ay = a.component(ky) - sy * a.component(kz);
+        let bx = b.component(kx) - sx * b.component(kz);
+        let by = b.component(ky) - sy * b.component(kz);
+        let cx = c.component(kx) - sx * c.component(kz);
+        let cy = c.component(ky) - sy * c.component(kz);
+
+        // Scaled barycentric coordinates
+        let u = cx * by - cy * bx;
+        let v = ax * cy - ay * cx;
+        let w = bx * ay - by * ax;
+
+        if (u < 0. || v < 0. || w < 0.) && (u > 0. || v > 0. || w > 0.) {
+            return None;
+        }
+
+        let det = u + v + w;
+        if det == 0. {
+            return None;
+        }
+
+        let t =
+            (u * sz * a.component(kz) + v * sz * b.component(kz) + w * sz * c.component(kz)) / det;
+
+        if t <= 0. {
+            return None;
+        }
+
+        Some((t, [u / det, v / det, w / det]))
+    }
 }
 
 impl Shape for Triangle {
@@ -28,37 +92,68 @@ impl Shape for Triangle {
     }
 
     fn collision_date(&self, ray: Ray) -> Option<f64> {
-        let n = (self.b - self.a) ^ (self.c - self.a);
+        self.intersect(ray).map(|(date, _)| date)
+    }
 
-        let lambda = (n * (self.a - ray.pos)) / (n * ray.dir);
+    fn collision(&self, ray: Ray) -> Option<Collision> {
+        let normal = ((self.b - self.a) ^ (self.c - self.a)).normalized();
 
-        if lambda <= 0. {
-            return None;
-        }
+        self.intersect(ray).map(|(date, [_, u, v])| Collision {
+            date,
+            pos: ray.pos_in(date),
+            normal,
+            uv: (u, v),
+        })
+    }
+}
 
-        let m = ray.pos() + lambda * ray.dir();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        // Check if M is in ABC
-        if ((self.b - self.a) ^ (m - self.a)) * ((m - self.a) ^ (self.c - self.a)) >= 0.
-            && ((self.a - self.b) ^ (m - self.b)) * ((m - self.b) ^ (self.c - self.b)) >= 0.
-            && ((self.a - self.c) ^ (m - self.c)) * ((m - self.c) ^ (self.b - self.c)) >= 0.
-        {
-            Some(lambda)
-        } else {
-            None
+    #[test]
+    fn test_watertight() {
+        // A fan of triangles around the origin
+        let n = 7;
+        let vertices: Vec<Vect> = (0..n)
+            .map(|i| {
+                let angle = 2. * std::f64::consts::PI * i as f64 / n as f64;
+                Vect::new(angle.cos(), angle.sin(), 0.3 * angle.sin())
+            })
+            .collect();
+        let triangles: Vec<Box<Triangle>> = (0..n)
+            .map(|i| Triangle::new(Vect::zero(), vertices[i], vertices[(i + 1) % n]))
+            .collect();
+
+        // Rays through the shared edges and the shared vertex
+        for &vertex in &vertices {
+            for k in 0..10 {
+                let target = (k as f64 / 10.) * vertex;
+                let ray = Ray::new(Vect::new(0.1, -0.2, 5.), target - Vect::new(0.1, -0.2, 5.));
+
+                assert!(triangles.iter().any(|t| t.collision_date(ray).is_some()));
+            }
         }
+
+        // Degenerate triangle
+        let triangle = Triangle::new(Vect::zero(), Vect::new(1., 1., 1.), Vect::new(2., 2., 2.));
+        let ray = Ray::new(Vect::new(1., 1., 5.), Vect::new(0., 0., -1.));
+        assert!(triangle.collision_date(ray).is_none());
     }
 
-    fn collision(&self, ray: Ray) -> Option<Collision> {
-        let normal = ((self.b - self.a) ^ (self.c - self.a)).normalized();
+    #[test]
+    fn test_barycentric_coordinates() {
+        let triangle = Triangle::new(
+            Vect::new(0., 0., 0.),
+            Vect::new(2., 0., 0.),
+            Vect::new(0., 2., 0.),
+        );
+        let ray = Ray::new(Vect::new(0.5, 1., 3.), Vect::new(0., 0., -1.));
 
-        match self.collision_date(ray) {
-            None => None,
-            Some(date) => Some(Collision {
-                date,
-                pos: ray.pos_in(date),
-                normal,
-            }),
-        }
+        let (date, [wa, wb, wc]) = triangle.intersect(ray).unwrap();
+        assert!((date - 3.).abs() <= 1e-9);
+        assert!((wa - 0.25).abs() <= 1e-9);
+        assert!((wb - 0.25).abs() <= 1e-9);
+        assert!((wc - 0.5).abs() <= 1e-9);
     }
 }
@@ -0,0 +1,305 @@
+//! Counters of the work done by the renders. Each thread updates its own
+//! counters, and the scheduler gathers them when the statistics of a render
+//! are asked with Renderer::render_with_stats. Counting is off unless the
+//! scheduler turns it on for its threads, because the statistics or the
+//! progress of the render are wanted, so that the other renders only pay for
+//! a check of a flag.
+
+use std::{cell::Cell, fmt, ops::Sub, time::Duration};
+
+/// Kind of a ray, for the counters
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RayKind {
+    /// Ray from the camera through a pixel
+    Camera,
+
+    /// Ray towards a light, to check whether it is visible
+    Shadow,
+
+    /// Ray bouncing on a surface
+    Indirect,
+}
+
+/// Counters of a thread
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Counters {
+    pub camera_rays: u64,
+    pub shadow_rays: u64,
+    pub indirect_rays: u64,
+    pub primitive_tests: u64,
+    pub node_tests: u64,
+    pub paths: u64,
+    pub path_segments: u64,
+}
+
+impl Counters {
+    pub fn rays(&self) -> u64 {
+        self.camera_rays + self.shadow_rays + self.indirect_rays
+    }
+}
+
+impl Sub for Counters {
+    type Output = Counters;
+
+    fn sub(self, other: Counters) -> Counters {
+        Counters {
+            camera_rays: self.camera_rays - other.camera_rays,
+            shadow_rays: self.shadow_rays - other.shadow_rays,
+            indirect_rays: self.indirect_rays - other.indirect_rays,
+            primitive_tests: self.primitive_tests - other.primitive_tests,
+            node_tests: self.node_tests - other.node_tests,
+            paths: self.paths - other.paths,
+            path_segments: self.path_segments - other.path_segments,
+        }
+    }
+}
+
+thread_local! {
+    static ENABLED: Cell<bool> = const { Cell::new(false) };
+    static COUNTERS: Cell<Counters> = Cell::new(Counters::default());
+}
+
+/// Turns the counting on or off for the current thread
+pub(crate) fn set_enabled(enabled: bool) {
+    ENABLED.with(|value| value.set(enabled));
+}
+
+fn update(f: impl FnOnce(&mut Counters)) {
+    if !ENABLED.with(Cell::get) {
+        return;
+    }
+
+    COUNTERS.with(|counters| {
+        let mut value = counters.get();
+        f(&mut value);
+        counters.set(value);
+    });
+}
+
+/// Counts a ray traced by the current thread
+pub fn count_ray(kind: RayKind) {
+    update(|counters| match kind {
+        RayKind::Camera => counters.camera_rays += 1,
+        RayKind::Shadow => counters.shadow_rays += 1,
+        RayKind::Indirect => counters.indirect_rays += 1,
+    });
+}
+
+/// Counts the intersection tests with the nodes of a hierarchy and with the
+/// primitives done to trace a ray
+pub fn count_tests(nodes: u64, primitives: u64) {
+    update(|counters| {
+        counters.node_tests += nodes;
+        counters.primitive_tests += primitives;
+    });
+}
+
+/// Counts a path made of a number of segments
+pub fn count_path(segments: u64) {
+    update(|counters| {
+        counters.paths += 1;
+        counters.path_segments += segments;
+    });
+}
+
+/// Returns the counters of the current thread
+pub(crate) fn counters() -> Counters {
+    COUNTERS.with(Cell::get)
+}
+
+/// Statistics of a render
+#[derive(Clone, Debug, Default)]
+pub struct RenderStats {
+    pub camera_rays: u64,
+    pub shadow_rays: u64,
+    pub indirect_rays: u64,
+
+    /// Intersection tests between rays and primitives
+    pub primitive_tests: u64,
+
+    /// Intersection tests between rays and the bounding boxes of the nodes of
+    /// the hierarchy
+    pub node_tests: u64,
+
+    pub paths: u64,
+    pub path_segments: u64,
+
+    /// Time spent rendering by each thread of the scheduler
+    pub thread_times: Vec<Duration>,
+
+    /// Memory used by the acceleration structures of the scene, in bytes
+    pub acceleration_memory: usize,
+
+    pub elapsed: Duration,
+}
+
+impl RenderStats {
+    pub fn rays(&self) -> u64 {
+        self.camera_rays + self.shadow_rays + self.indirect_rays
+    }
+
+    /// Returns the average number of segments of the paths
+    pub fn average_path_length(&self) -> f64 {
+        self.path_segments as f64 / self.paths.max(1) as f64
+    }
+
+    /// Adds the counters of a thread, and the time it spent
+    pub(crate) fn add(&mut self, thread: usize, counters: Counters, time: Duration) {
+        self.camera_rays += counters.camera_rays;
+        self.shadow_rays += counters.shadow_rays;
+        self.indirect_rays += counters.indirect_rays;
+        self.primitive_tests += counters.primitive_tests;
+        self.node_tests += counters.node_tests;
+        self.paths += counters.paths;
+        self.path_segments += counters.path_segments;
+
+        if self.thread_times.len() <= thread {
+            self.thread_times.resize(thread + 1, Duration::ZERO);
+        }
+        self.thread_times[thread] += time;
+    }
+}
+
+impl fmt::Display for RenderStats {
+    /// Formats the statistics as a table
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let seconds = self.elapsed.as_secs_f64().max(1e-9);
+        let per_ray = |count: u64| count as f64 / self.rays().max(1) as f64;
+        let mut row = |name: &str, value: String| writeln!(f, "{name:<24}{value:>20}");
+
+        row("Time", format!("{seconds:.3} s"))?;
+        row("Rays", self.rays().to_string())?;
+        row("  camera", self.camera_rays.to_string())?;
+        row("  shadow", self.shadow_rays.to_string())?;
+        row("  indirect", self.indirect_rays.to_string())?;
+        row(
+            "Rays per second",
+            format!("{:.0}", self.rays() as f64 / seconds),
+        )?;
+        row(
+            "Primitive tests",
+            format!(
+                "{} ({:.1}/ray)",
+                self.primitive_tests,
+                per_ray(self.primitive_tests)
+            ),
+        )?;
+        row(
+            "Node tests",
+            format!("{} ({:.1}/ray)", self.node_tests, per_ray(self.node_tests)),
+        )?;
+        row(
+            "Average path length",
+            format!("{:.2}", self.average_path_length()),
+        )?;
+        row(
+            "Acceleration memory",
+            format!("{:.1} KiB", self.acceleration_memory as f64 / 1024.),
+        )?;
+        for (thread, time) in self.thread_times.iter().enumerate() {
+            row(
+                &format!("Thread {thread}"),
+                format!("{:.3} s", time.as_secs_f64()),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{count_ray, counters, RayKind};
+    use crate::cameras::PerspectiveCamera;
+    use crate::primitives::{GeometricPrimitive, TransformedPrimitive};
+    use crate::renderers::WhittedRayTracer;
+    use crate::shapes::{CompoundShape, Sphere};
+    use crate::{
+        Color, Light, Material, Primitive, Renderer, Scene, Scheduler, Shape, Transform, Vect,
+    };
+    use std::{collections::HashSet, sync::Arc};
+
+    #[test]
+    fn test_render_stats() {
+        let mut scene = Scene::new(PerspectiveCamera::new(
+            Vect::new(-5., 0., 0.),
+            Vect::new(0., 0., 0.),
+            Vect::new(0., 0., 1.),
+            40.,
+            8,
+            6,
+        ));
+        for i in 0..8 {
+            scene.add_primitive(GeometricPrimitive::new(
+                Sphere::new(Vect::new(0., i as f64 - 3.5, 0.), 0.4),
+                Material::new(Color::white()),
+            ));
+        }
+        scene.add_light(Light {
+            pos: Vect::new(-5., 0., 5.),
+            intensity: 1.,
+        });
+
+        let renderer = WhittedRayTracer::new().with_scheduler(Scheduler::new(2).with_tile_size(4));
+        let (_, stats) = renderer.render_with_stats(scene);
+
+        // One camera ray per pixel, and one shadow ray per camera ray hitting
+        // a sphere
+        assert_eq!(stats.camera_rays, 8 * 6);
+        assert!(stats.shadow_rays > 0 && stats.shadow_rays < stats.camera_rays);
+        assert_eq!(stats.indirect_rays, 0);
+        assert_eq!(stats.average_path_length(), 1.);
+
+        // The hierarchy avoids testing all the spheres
+        assert!(stats.node_tests > 0);
+        assert!(stats.primitive_tests < 8 * stats.rays());
+
+        assert_eq!(stats.thread_times.len(), 2);
+        assert!(stats.acceleration_memory > 0);
+        assert!(stats.to_string().contains("Node tests"));
+
+        // Nothing is counted outside the threads of a scheduler collecting
+        // statistics
+        count_ray(RayKind::Camera);
+        assert_eq!(counters().rays(), 0);
+    }
+
+    #[test]
+    fn test_acceleration_memory() {
+        let camera = PerspectiveCamera::new(
+            Vect::new(-5., 0., 0.),
+            Vect::new(0., 0., 0.),
+            Vect::new(0., 0., 1.),
+            40.,
+            8,
+            6,
+        );
+
+        let mut mesh = CompoundShape::new();
+        for i in 0..20 {
+            mesh.add(Sphere::new(Vect::new(0., i as f64, 0.), 0.4));
+        }
+        let mesh_memory = mesh.memory_size(&mut HashSet::new());
+        assert!(mesh_memory > 0);
+
+        let shared: Arc<dyn Primitive> = Arc::from(GeometricPrimitive::new(
+            mesh,
+            Material::new(Color::white()),
+        ) as Box<dyn Primitive>);
+        let memory = |instances: usize| {
+            let mut scene = Scene::new(camera.clone());
+            for i in 0..instances {
+                scene.add_primitive(TransformedPrimitive::new_instance(
+                    Arc::clone(&shared),
+                    Transform::new_translation(Vect::new(i as f64, 0., 0.)),
+                ));
+            }
+            scene.acceleration_memory()
+        };
+
+        // The hierarchy of the mesh is counted, once whatever the number of
+        // instances
+        assert!(memory(1) > mesh_memory);
+        assert!(memory(2) < memory(1) + mesh_memory);
+    }
+}
@@ -12,7 +12,7 @@ impl<T: Clone> Uniform<T> {
 }
 
 impl<T: Clone> Texture<T> for Uniform<T> {
-    fn get(&self, uv: (f64, f64)) -> T {
+    fn get(&self, _uv: (f64, f64)) -> T {
         self.val.clone()
     }
 }
@@ -1,4 +1,4 @@
-use crate::{matrix::Matrix4x4, ray::Ray, vect::Vect};
+use crate::{matrix::Matrix4x4, ray::Ray, vect::Vect, BoundingBox};
 
 #[derive(Clone, Debug)]
 pub struct Transform {
@@ -15,6 +15,17 @@ impl Transform {
         }
     }
 
+    /// Returns the transformation given by a matrix, which must be invertible
+    pub fn new_from_matrix(mat: Matrix4x4) -> Self {
+        let mat_inv = mat.inverse();
+        Self { mat, mat_inv }
+    }
+
+    /// Returns the matrix of the transformation
+    pub fn matrix(&self) -> &Matrix4x4 {
+        &self.mat
+    }
+
     /// Returns a translation
     pub fn new_translation(v: Vect) -> Self {
         let mat = Matrix4x4::new([[1., 0., 0., v.x], [0., 1., 0., v.y], [0., 0., 1., v.z]]);
@@ -178,11 +189,34 @@ impl Transform {
         }
     }
 
+    /// Returns a box containing the image of a box by the transformation
+    pub fn apply_bounding_box(&self, bounding_box: &BoundingBox) -> BoundingBox {
+        if !bounding_box.is_finite() {
+            return BoundingBox::new_full();
+        }
+
+        let mut result = BoundingBox::new();
+
+        if let Some((min, max)) = bounding_box.extremities() {
+            for i in 0..8 {
+                let corner = Vect::new(
+                    if i & 1 == 0 { min.x } else { max.x },
+                    if i & 2 == 0 { min.y } else { max.y },
+                    if i & 4 == 0 { min.z } else { max.z },
+                );
+                result.add_point(self.apply_point(corner));
+            }
+        }
+
+        result
+    }
+
     /// Applies the transformation to a ray
     pub fn apply_ray(&self, ray: Ray) -> Ray {
         Ray {
             pos: self.apply_point(ray.pos),
             dir: self.apply_vector(ray.dir),
+            time: ray.time,
         }
     }
 
@@ -191,6 +225,7 @@ impl Transform {
         Ray {
             pos: self.apply_inv_point(ray.pos),
             dir: self.apply_inv_vector(ray.dir),
+            time: ray.time,
         }
     }
 }
@@ -206,7 +241,6 @@ pub fn merge(t1: &Transform, t2: &Transform) -> Transform {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::f64::consts::PI;
 
     #[test]
     fn test_transforms_from_basis() {
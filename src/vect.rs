@@ -163,6 +163,7 @@ mod tests {
     use super::*;
 
     #[test]
+    #[allow(clippy::approx_constant, clippy::manual_range_contains)]
     fn test_norm() {
         let mut v = Vect::new(1., -1., 0.);
         let norm = v.norm();
@@ -9,6 +9,28 @@ pub fn to_uniform_disk(sample: [f64; 2]) -> Vect {
     Vect::new(r * phi.cos(), r * phi.sin(), 0.)
 }
 
+/// Maps a sample to a regular polygon inscribed in the unit disk, with a
+/// vertex at the given angle
+pub fn to_uniform_polygon(sample: [f64; 2], sides: usize, rotation: f64) -> Vect {
+    // Choose a triangle of the polygon, and reuse the rest of the sample
+    let scaled = sample[0] * sides as f64;
+    let triangle = (scaled as usize).min(sides - 1);
+    let s = scaled - triangle as f64;
+
+    let angle = |i: usize| rotation + 2. * PI * i as f64 / sides as f64;
+    let (a1, a2) = (angle(triangle), angle(triangle + 1));
+
+    // Uniform point in the triangle formed by the center and two vertices
+    let r = s.sqrt();
+    let (w1, w2) = (r * (1. - sample[1]), r * sample[1]);
+
+    Vect::new(
+        w1 * a1.cos() + w2 * a2.cos(),
+        w1 * a1.sin() + w2 * a2.sin(),
+        0.,
+    )
+}
+
 pub fn to_uniform_sphere(sample: [f64; 2]) -> Vect {
     let z = 2. * sample[0] - 1.;
     let r = (1. - z * z).sqrt();
@@ -50,6 +72,7 @@ mod tests {
     use fastrand::Rng;
 
     #[test]
+    #[allow(clippy::approx_constant, clippy::manual_range_contains)]
     fn test_uniform_directed_hemisphere() {
         let mut rng = Rng::with_seed(42);
 
@@ -69,4 +92,37 @@ mod tests {
         v.normalize();
         assert!(0.99 <= v.norm() && v.norm() <= 1.01);
     }
+
+    #[test]
+    fn test_uniform_polygon() {
+        let mut rng = Rng::with_seed(7);
+        let (sides, rotation) = (6, 0.3);
+        let vertex = |i: usize| {
+            let angle = rotation + 2. * PI * i as f64 / sides as f64;
+            Vect::new(angle.cos(), angle.sin(), 0.)
+        };
+
+        let nb_samples = 60000;
+        let mut counts = vec![0; sides];
+
+        for _ in 0..nb_samples {
+            let p = to_uniform_polygon([rng.f64(), rng.f64()], sides, rotation);
+
+            // On the inner side of all the edges
+            for i in 0..sides {
+                let (a, b) = (vertex(i), vertex(i + 1));
+                let edge = b - a;
+                assert!(edge.x * (p.y - a.y) - edge.y * (p.x - a.x) >= -1e-12);
+            }
+
+            let angle = (p.y.atan2(p.x) - rotation).rem_euclid(2. * PI);
+            counts[((angle / (2. * PI / sides as f64)) as usize).min(sides - 1)] += 1;
+        }
+
+        // The triangles have the same area, so the same number of samples
+        let expected = nb_samples as f64 / sides as f64;
+        for count in counts {
+            assert!((count as f64 - expected).abs() < 0.03 * expected);
+        }
+    }
 }